@@ -1,10 +1,7 @@
 #version 330
 
-uniform vec4 eye_pos;
-uniform vec4 light_pos;
+#include "include/camera.glsl"
 
-uniform mat4 projection;
-uniform mat4 view;
 uniform mat4 model;
 
 in vec4 projected_position;
@@ -19,6 +16,10 @@ struct pLight {
 	vec3 specular;
 };
 
+layout(std140) uniform Light {
+	pLight light;
+};
+
 vec3 refl(vec3 N, vec3 L)
 {
 	return 2.0 * dot(L, N) * N - L;
@@ -37,9 +38,8 @@ vec3 phong(vec3 hit, vec3 eye, vec3 N, pLight light, float ks)
 
 void main()
 {
-	pLight l1 = pLight(
-		(light_pos * model * view).xyz,
-		vec3(0.1), vec3(0.5), vec3(0.8));
+	pLight l1 = light;
+	l1.position = (vec4(light.position, 1.0) * model * view).xyz;
 
 	color = vec4(phong(projected_position.xyz, eye_pos.xyz, transposed_normal.xyz, l1, 1.0), 1.0);
 }
\ No newline at end of file
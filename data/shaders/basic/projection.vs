@@ -4,8 +4,8 @@ layout(location = 0) in vec3 position;
 layout(location = 1) in vec3 normal;
 layout(location = 2) in vec2 uv;
 
-uniform mat4 projection;
-uniform mat4 view;
+#include "include/camera.glsl"
+
 uniform mat4 model;
 
 out vec4 projected_position;
@@ -0,0 +1,7 @@
+#pragma once
+
+layout(std140) uniform Camera {
+	mat4 projection;
+	mat4 view;
+	vec4 eye_pos;
+};
@@ -0,0 +1,5 @@
+#pragma once
+
+float random(vec2 st) { return fract(sin(dot(st.xy, vec2(12.9898, 78.233))) * 43758.5453123); }
+
+float remap(float v, float l1, float h1, float l2, float h2) { return l2 + (v - l1) * (h2 - l2) / (h1 - l1); }
@@ -8,7 +8,7 @@ uniform float time;
 uniform float dt;
 uniform float speed;
 
-float random(vec2 st) { return fract(sin(dot(st.xy, vec2(12.9898, 78.233))) * 43758.5453123); }
+#include "include/random.glsl"
 
 void main(void)
 {
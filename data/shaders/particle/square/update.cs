@@ -8,41 +8,7 @@ uniform float dt;
 uniform vec3 square;
 uniform vec3 density;
 
-float random(vec2 st) { return fract(sin(dot(st.xy, vec2(12.9898, 78.233))) * 43758.5453123); }
-float remap(float v, float l1, float h1, float l2, float h2) { return l2 + (v - l1) * (h2 - l2) / (h1 - l1); }
-
-// Based on Morgan McGuire @morgan3d
-// https://www.shadertoy.com/view/4dS3Wd
-float noise(in vec2 st)
-{
-	vec2 i = floor(st);
-	vec2 f = fract(st);
-
-	// Four corners in 2D of a tile
-	float a = random(i);
-	float b = random(i + vec2(1.0, 0.0));
-	float c = random(i + vec2(0.0, 1.0));
-	float d = random(i + vec2(1.0, 1.0));
-
-	vec2 u = f * f * (3.0 - 2.0 * f);
-
-	return mix(a, b, u.x) + (c - a) * u.y * (1.0 - u.x) + (d - b) * u.x * u.y;
-}
-
-float fbm(in vec2 st)
-{
-	// Initial values
-	float value = 0.0;
-	float amplitude = 0.5;
-	//
-	// Loop of octaves
-	for (int i = 0; i < 5; i++) {
-		value += amplitude * noise(st);
-		st *= 2.;
-		amplitude *= .5;
-	}
-	return value;
-}
+#include "include/noise.glsl"
 
 void main(void)
 {
@@ -2,8 +2,8 @@
 
 layout(location = 0) in vec3 position;
 
-uniform mat4 projection;
-uniform mat4 view;
+#include "include/camera.glsl"
+
 uniform mat4 model;
 
 smooth out vec4 interpol;
@@ -3,7 +3,9 @@
 
 #define FXAA_PC 1
 #define FXAA_GLSL_130 1
+#ifndef FXAA_QUALITY__PRESET
 #define FXAA_QUALITY__PRESET 39
+#endif
 
 /*============================================================================
 
@@ -14,10 +14,7 @@ uniform sampler2D screenTexture;
 #define PI 3.141592
 #define saturate(x) (clamp((x), 0.0, 1.0))
 
-float hash2(vec2 n)
-{
-	return fract(sin(dot(n.xy, vec2(12.9898, 78.233))) * 43758.5453);
-}
+#include "include/random.glsl"
 
 vec2 remap(vec2 t, vec2 a, vec2 b) { return clamp((t - a) / (b - a), 0.0, 1.0); }
 
@@ -58,7 +55,7 @@ void main()
 	sum_color += elysian_aberation(uv, ABERRATION_AMOUNT);
 
 	// Fake film grain
-	sum_color += ((0.5 - hash2(uv * cos(time))) / 20.0);
+	sum_color += ((0.5 - random(uv * cos(time))) / 20.0);
 
 	// Gamma correction
 	sum_color = pow(sum_color, vec3(2.2));
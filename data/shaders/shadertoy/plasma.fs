@@ -0,0 +1,7 @@
+// Shadertoy style shader, run with `peglrs --shadertoy data/shaders/shadertoy/plasma.fs`.
+void mainImage(out vec4 fragColor, in vec2 fragCoord)
+{
+	vec2 uv = fragCoord / iResolution.xy;
+	vec3 col = 0.5 + 0.5 * cos(iTime + uv.xyx + vec3(0.0, 2.0, 4.0));
+	fragColor = vec4(col, 1.0);
+}
@@ -0,0 +1,8 @@
+// Run with `peglrs --shadertoy data/shaders/shadertoy/trail.fs --buffer-a data/shaders/shadertoy/trail_a.fs`.
+void mainImage(out vec4 fragColor, in vec2 fragCoord)
+{
+	vec2 uv = fragCoord / iResolution.xy;
+	float trail = texture(iChannel0, uv).r;
+	vec3 col = mix(vec3(0.02, 0.04, 0.08), vec3(1.0, 0.6, 0.2), trail);
+	fragColor = vec4(col, 1.0);
+}
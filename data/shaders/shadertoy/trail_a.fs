@@ -0,0 +1,8 @@
+// Buffer A of trail.fs: paints under the mouse and fades what it drew before.
+void mainImage(out vec4 fragColor, in vec2 fragCoord)
+{
+	vec4 previous = texture(iChannel0, fragCoord / iResolution.xy);
+	float brush = smoothstep(12.0, 8.0, distance(fragCoord, iMouse.xy));
+	float paint = iMouse.z > 0.0 ? brush : 0.0;
+	fragColor = vec4(max(previous.rgb * 0.98, vec3(paint)), 1.0);
+}
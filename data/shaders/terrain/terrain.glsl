@@ -0,0 +1,11 @@
+#version 410 core
+#pragma stage fragment
+
+in float elevation;
+
+out vec4 color;
+
+void main()
+{
+	color = vec4(mix(vec3(0.1, 0.3, 0.1), vec3(0.9), elevation), 1.0);
+}
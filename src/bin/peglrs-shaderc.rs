@@ -0,0 +1,193 @@
+extern crate peglrs;
+
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use peglrs::shaders::manifest::ProgramManifest;
+use peglrs::shaders::preprocessor::Preprocessor;
+use peglrs::shaders::validation::{self, DeclaredUniforms, ValidationError};
+use peglrs::shaders::{get_pragma_shader_type, get_shader_type, ShaderError, ShaderType};
+use peglrs::shadertoy;
+use peglrs::utils;
+
+// Validates every shader without a GL context, for machines without display:
+//
+//     peglrs-shaderc [SHADER_DIR] [SOURCE_DIR]
+//
+// SHADER_DIR (data/shaders by default) is also the include root. Manifest
+// stages are checked with the manifest defines, the other files on their own,
+// wrapped like the shadertoy runner does when they follow its conventions.
+// Files without a stage are skipped when included by another shader. Uniform
+// names used by the Rust code under SOURCE_DIR (src by default) must be
+// declared by at least one shader. Exits with 1 when anything failed.
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some(extension)
+}
+
+// Preprocesses and parses a shader. `shader_type` overrides the stage detected
+// from the extension or a `#pragma stage`, like in manifests. The included
+// files are recorded even when the shader itself is broken.
+fn check_shader(
+    path: &Path,
+    shader_type: Option<ShaderType>,
+    preprocessor: &Preprocessor,
+    included: &mut HashSet<PathBuf>,
+    declared: &mut DeclaredUniforms,
+) -> Result<(), ShaderError> {
+    let processed = preprocessor.process(path)?;
+    included.extend(processed.source_map.files.iter().skip(1).cloned());
+
+    shader_type
+        .or_else(|| get_shader_type(path))
+        .or_else(|| get_pragma_shader_type(&processed.source))
+        .ok_or_else(|| ShaderError::UnknownExtension(path.to_path_buf()))?;
+
+    declared.extend(validation::parse_shader(path, &processed)?);
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let shader_dir = PathBuf::from(args.first().map_or("data/shaders", String::as_str));
+    let source_dir = PathBuf::from(args.get(1).map_or("src", String::as_str));
+
+    let mut files = Vec::new();
+    if let Err(error) = collect_files(&shader_dir, &mut files) {
+        eprintln!("[ERR] Couldn't list {} : {}", shader_dir.display(), error);
+        process::exit(1);
+    }
+    files.sort();
+
+    let preprocessor = Preprocessor {
+        include_roots: vec![shader_dir.clone()],
+        ..Preprocessor::default()
+    };
+    let mut errors: Vec<ValidationError> = Vec::new();
+    let mut declared = DeclaredUniforms::default();
+    let mut checked: HashSet<PathBuf> = HashSet::new();
+    let mut included: HashSet<PathBuf> = HashSet::new();
+    let mut undetected: Vec<(PathBuf, ShaderError)> = Vec::new();
+
+    for manifest_path in files.iter().filter(|path| has_extension(path, "prog")) {
+        let manifest = match ProgramManifest::load(manifest_path) {
+            Ok(manifest) => manifest,
+            Err(error) => {
+                errors.push(error.into());
+                continue;
+            }
+        };
+
+        let mut manifest_preprocessor = manifest.preprocessor();
+        manifest_preprocessor.include_roots = preprocessor.include_roots.clone();
+        for (stage, path) in &manifest.stages {
+            checked.insert(path.clone());
+            let checked_shader = check_shader(
+                path,
+                Some(*stage),
+                &manifest_preprocessor,
+                &mut included,
+                &mut declared,
+            );
+            if let Err(error) = checked_shader {
+                errors.push(error.into());
+            }
+        }
+    }
+
+    for path in &files {
+        // SPIR-V modules were already validated by the compiler producing them.
+        if has_extension(path, "prog") || has_extension(path, "spv") || checked.contains(path) {
+            continue;
+        }
+
+        let shadertoy = utils::load_file(path)
+            .map(|src| shadertoy::is_shadertoy_source(&src))
+            .unwrap_or(false);
+        let checked_shader = if shadertoy {
+            let mut shadertoy_preprocessor = shadertoy::preprocessor();
+            shadertoy_preprocessor.include_roots = preprocessor.include_roots.clone();
+            check_shader(
+                path,
+                Some(ShaderType::FRAGMENT),
+                &shadertoy_preprocessor,
+                &mut included,
+                &mut declared,
+            )
+        } else {
+            check_shader(path, None, &preprocessor, &mut included, &mut declared)
+        };
+
+        match checked_shader {
+            Ok(()) => {}
+            Err(error @ ShaderError::UnknownExtension(_)) => undetected.push((path.clone(), error)),
+            Err(error) => errors.push(error.into()),
+        }
+    }
+
+    // Include only files are known once every shader was preprocessed. The
+    // preprocessor records included files by their canonical path.
+    for (path, error) in undetected {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if !included.contains(&canonical) {
+            errors.push(error.into());
+        }
+    }
+
+    let mut sources = Vec::new();
+    if let Err(error) = collect_files(&source_dir, &mut sources) {
+        eprintln!("[ERR] Couldn't list {} : {}", source_dir.display(), error);
+        process::exit(1);
+    }
+    sources.sort();
+
+    for path in sources.iter().filter(|path| has_extension(path, "rs")) {
+        let src = match utils::load_file(path) {
+            Ok(src) => src,
+            Err(error) => {
+                eprintln!("[ERR] Couldn't read {} : {}", path.display(), error);
+                process::exit(1);
+            }
+        };
+
+        for reference in validation::referenced_uniforms(&src) {
+            if !declared.contains(&reference) {
+                errors.push(ValidationError::UnknownUniform {
+                    path: path.clone(),
+                    line: reference.line,
+                    name: reference.name,
+                });
+            }
+        }
+    }
+
+    for error in &errors {
+        eprintln!("[ERR] {}", error);
+    }
+
+    println!(
+        "[NFO] {} shader files checked, {} errors",
+        files.len(),
+        errors.len()
+    );
+
+    if !errors.is_empty() {
+        process::exit(1);
+    }
+}
@@ -1,5 +1,7 @@
 use cgmath::prelude::*;
-use cgmath::{perspective, Deg, Matrix4, Point3, Vector3};
+use cgmath::{perspective, Deg, Matrix4, Point3, Rad, Vector3, Vector4};
+
+use crate::mesh::bounds::BoundingSphere;
 
 pub enum Direction {
     FORWARD,
@@ -28,6 +30,20 @@ pub struct Camera {
     angle_v: f32,
 }
 
+// Matches the `Camera` uniform block of data/shaders/include/camera.glsl.
+#[derive(Debug)]
+pub struct CameraBlock {
+    pub projection: Matrix4<f32>,
+    pub view: Matrix4<f32>,
+    pub eye_pos: Vector4<f32>,
+}
+
+crate::impl_std140!(CameraBlock {
+    projection,
+    view,
+    eye_pos
+});
+
 impl Camera {
     pub fn new(position: Point3<f32>, forward: Vector3<f32>, world_up: Vector3<f32>) -> Camera {
         let right = forward.cross(world_up).normalize();
@@ -48,6 +64,31 @@ impl Camera {
         Matrix4::look_at(self.position, self.position + self.forward, self.up)
     }
 
+    pub fn uniform_block(&self, projection: Matrix4<f32>) -> CameraBlock {
+        CameraBlock {
+            projection,
+            view: self.view(),
+            eye_pos: self.position.to_homogeneous(),
+        }
+    }
+
+    // Moves the camera along its forward direction so that `sphere` is
+    // centered and fits the perspective projection of vertical field of view
+    // `fov_y` and `aspect` ratio. The near and far planes are left to the
+    // caller, the center is `radius / sin(fov / 2)` away. A sphere without
+    // radius, e.g. around a single point, is only centered at the current
+    // distance.
+    pub fn frame<A: Into<Rad<f32>>>(&mut self, sphere: &BoundingSphere, fov_y: A, aspect: f32) {
+        let half_y = fov_y.into().0 / 2.0;
+        let half_x = (half_y.tan() * aspect).atan();
+        let distance = if sphere.radius > 0.0 {
+            sphere.radius / half_y.min(half_x).sin()
+        } else {
+            self.position.distance(sphere.center)
+        };
+        self.position = sphere.center - self.forward.normalize() * distance;
+    }
+
     pub fn move_cam(&mut self, dir: &Direction, dt: f32) {
         let vel = 5.0 * dt;
         match dir {
@@ -76,3 +117,46 @@ impl Camera {
         self.up = self.right.cross(self.forward).normalize();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Deg;
+
+    fn camera() -> Camera {
+        Camera::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -1.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn frame_fits_the_sphere_in_the_narrowest_fov() {
+        let sphere = BoundingSphere::new(Point3::new(1.0, 2.0, -3.0), 1.0);
+
+        // 90 degrees: the center is sqrt(2) radii away.
+        let mut camera = camera();
+        camera.frame(&sphere, Deg(90.0), 1.0);
+        let expected = Point3::new(1.0, 2.0, -3.0 + 2.0f32.sqrt());
+        assert!(camera.position.distance(expected) < 1e-5);
+
+        // Wider than tall, the vertical fov still limits.
+        camera.frame(&sphere, Deg(60.0), 2.0);
+        assert!((camera.position.distance(sphere.center) - 2.0).abs() < 1e-5);
+
+        // Taller than wide, the horizontal fov of 60 degrees limits.
+        let aspect = 30f32.to_radians().tan() / 45f32.to_radians().tan();
+        camera.frame(&sphere, Deg(90.0), aspect);
+        assert!((camera.position.distance(sphere.center) - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn frame_of_a_point_keeps_the_distance() {
+        let mut camera = camera();
+        let point = BoundingSphere::new(Point3::new(0.0, 0.0, -4.0), 0.0);
+        camera.frame(&point, Deg(60.0), 1.0);
+        assert!(camera.position.x.is_finite());
+        assert!(camera.position.distance(Point3::new(0.0, 0.0, 0.0)) < 1e-5);
+    }
+}
@@ -0,0 +1,12 @@
+extern crate cgmath;
+extern crate gl;
+extern crate gl_loader;
+extern crate glsl_lang;
+
+pub mod camera;
+pub mod frame;
+pub mod mesh;
+pub mod scene;
+pub mod shaders;
+pub mod shadertoy;
+pub mod utils;
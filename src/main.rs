@@ -2,25 +2,41 @@ extern crate cgmath;
 extern crate gl;
 extern crate gl_loader;
 extern crate glutin;
-
-mod camera;
-mod frame;
-mod mesh;
-mod scene;
-mod shaders;
-mod utils;
+extern crate peglrs;
 
 use glutin::{GlContext, GlWindow};
+use std::env;
 use std::path::Path;
-use std::rc::Rc;
 use std::time::{Duration, Instant};
 
-use camera::{Camera, Direction};
-use shaders::{Program, Shader};
+use peglrs::camera::{Camera, Direction};
+use peglrs::impl_std140;
+use peglrs::mesh;
+use peglrs::shaders::binary_cache;
+use peglrs::shaders::manifest::ProgramLibrary;
+use peglrs::shaders::uniform_buffer::UniformBuffer;
+use peglrs::shaders::watcher::ShaderWatcher;
+use peglrs::shadertoy::{Buffer, Shadertoy};
 
 use cgmath::prelude::*;
 use cgmath::{perspective, Deg, Matrix4, Point3, Vector3};
 
+// Matches the `Light` uniform block of data/shaders/basic/phong/phong.fs.
+#[derive(Debug)]
+struct LightBlock {
+    position: Vector3<f32>,
+    ambiant: Vector3<f32>,
+    diffuse: Vector3<f32>,
+    specular: Vector3<f32>,
+}
+
+impl_std140!(LightBlock {
+    position,
+    ambiant,
+    diffuse,
+    specular
+});
+
 fn resize_window(window: &GlWindow, projection: &mut Matrix4<f32>) {
     let dpi = window.get_hidpi_factor();
     let wlsize = window.get_inner_size().unwrap();
@@ -39,6 +55,122 @@ fn resize_window(window: &GlWindow, projection: &mut Matrix4<f32>) {
     );
 }
 
+fn physical_size(window: &GlWindow) -> glutin::dpi::PhysicalSize {
+    window
+        .get_inner_size()
+        .unwrap()
+        .to_physical(window.get_hidpi_factor())
+}
+
+const SHADERTOY_USAGE: &str =
+    "usage: peglrs --shadertoy <image.fs> [--buffer-a <a.fs>] ... [--buffer-d <d.fs>]";
+
+// `peglrs --shadertoy image.fs --buffer-a a.fs` renders a Shadertoy style
+// shader full-screen, iChannel0 to iChannel3 being Buffer A to D.
+fn run_shadertoy(args: &[String], events_loop: &mut glutin::EventsLoop, gl_window: &GlWindow) {
+    let image = match args.first() {
+        Some(image) if !image.starts_with("--") => Path::new(image),
+        _ => {
+            eprintln!("{}", SHADERTOY_USAGE);
+            return;
+        }
+    };
+
+    let wpsize = physical_size(gl_window);
+    let mut height = wpsize.height as i32;
+    let mut toy = match Shadertoy::new(image, wpsize.width as i32, height) {
+        Ok(toy) => toy,
+        Err(err) => {
+            eprintln!("[ERR] {}", err);
+            return;
+        }
+    };
+
+    for option in args[1..].chunks(2) {
+        let buffer = match option[0].as_str() {
+            "--buffer-a" => Buffer::A,
+            "--buffer-b" => Buffer::B,
+            "--buffer-c" => Buffer::C,
+            "--buffer-d" => Buffer::D,
+            _ => {
+                eprintln!("{}", SHADERTOY_USAGE);
+                return;
+            }
+        };
+        let path = match option.get(1) {
+            Some(path) => Path::new(path),
+            None => {
+                eprintln!("{}", SHADERTOY_USAGE);
+                return;
+            }
+        };
+        toy = match toy.with_buffer(buffer, path) {
+            Ok(toy) => toy,
+            Err(err) => {
+                eprintln!("[ERR] {}", err);
+                return;
+            }
+        };
+    }
+
+    let mut shader_watcher = ShaderWatcher::new(Duration::from_millis(500));
+    for program in toy.programs() {
+        shader_watcher.watch(&program);
+    }
+
+    let mut time = Instant::now();
+    let mut mouse = (0.0, 0.0);
+    let mut mouse_pressed = false;
+
+    let mut running = true;
+    while running {
+        events_loop.poll_events(|event| {
+            if let glutin::Event::WindowEvent { event, .. } = event {
+                match event {
+                    glutin::WindowEvent::CloseRequested => running = false,
+                    glutin::WindowEvent::KeyboardInput { input, .. }
+                        if input.virtual_keycode == Some(glutin::VirtualKeyCode::Escape) =>
+                    {
+                        running = false
+                    }
+                    glutin::WindowEvent::Resized(_)
+                    | glutin::WindowEvent::HiDpiFactorChanged(_) => {
+                        let wpsize = physical_size(gl_window);
+                        gl_window.resize(wpsize);
+                        height = wpsize.height as i32;
+                        toy.resize(wpsize.width as i32, height);
+                    }
+                    glutin::WindowEvent::CursorMoved { position, .. } => {
+                        let position = position.to_physical(gl_window.get_hidpi_factor());
+                        mouse = (position.x as f32, height as f32 - position.y as f32);
+                    }
+                    glutin::WindowEvent::MouseInput {
+                        state,
+                        button: glutin::MouseButton::Left,
+                        ..
+                    } => mouse_pressed = state == glutin::ElementState::Pressed,
+                    _ => (),
+                }
+            }
+        });
+
+        for err in shader_watcher.poll() {
+            eprintln!("\n[ERR] {}", err);
+        }
+
+        let dt = time.elapsed().as_secs_f32();
+        time = Instant::now();
+
+        toy.set_mouse(mouse.0, mouse.1, mouse_pressed);
+        if let Err(err) = toy.render(dt) {
+            eprintln!("\n[ERR] {}", err);
+        }
+
+        gl_window.swap_buffers().unwrap();
+        print!("\r{:.8} ms", dt * 1000.0);
+    }
+}
+
 fn main() {
     let mut events_loop = glutin::EventsLoop::new();
     let window = glutin::WindowBuilder::new()
@@ -47,7 +179,11 @@ fn main() {
         .with_decorations(true)
         .with_transparency(false);
     //    .with_fullscreen(Some(events_loop.get_primary_monitor()));
-    let context = glutin::ContextBuilder::new().with_vsync(false);
+    // 4.3 for the program interface queries of the shader reflection.
+    let context = glutin::ContextBuilder::new()
+        .with_gl(glutin::GlRequest::Specific(glutin::Api::OpenGl, (4, 3)))
+        .with_gl_profile(glutin::GlProfile::Core)
+        .with_vsync(false);
     let gl_window = glutin::GlWindow::new(window, context, &events_loop).unwrap();
     let dpi = gl_window.get_hidpi_factor();
     unsafe {
@@ -70,14 +206,19 @@ fn main() {
         gl::Viewport(0, 0, wpsize.width as i32, wpsize.height as i32);
     }
 
-    let vertex_shader = Shader::load_shader(Path::new("data/shaders/basic/projection.vs"));
-    let pixel_shader = Shader::load_shader(Path::new("data/shaders/basic/phong/phong.fs"));
+    binary_cache::enable(Path::new(".program-cache"));
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("--shadertoy") {
+        run_shadertoy(&args[1..], &mut events_loop, &gl_window);
+        return;
+    }
+
+    let mut programs = ProgramLibrary::new(Path::new("data/shaders"));
+    let program = programs.get("basic/phong").unwrap();
 
-    let program = Program::load_program(&vec![
-        Rc::new(pixel_shader.unwrap()),
-        Rc::new(vertex_shader.unwrap()),
-    ])
-    .unwrap();
+    let mut shader_watcher = ShaderWatcher::new(Duration::from_millis(500));
+    shader_watcher.watch(&program);
 
     let mut cube = mesh::Mesh::cube();
     cube.ready_up();
@@ -96,6 +237,16 @@ fn main() {
         Vector3::new(0.0, 1.0, 0.0),
     );
 
+    let mut camera_ubo = UniformBuffer::new(&cam.uniform_block(projection));
+    let light_ubo = UniformBuffer::new(&LightBlock {
+        position: Vector3::new(3.0, 1.0, 1.0),
+        ambiant: Vector3::new(0.1, 0.1, 0.1),
+        diffuse: Vector3::new(0.5, 0.5, 0.5),
+        specular: Vector3::new(0.8, 0.8, 0.8),
+    });
+
+    let mut blocks_bound_to = 0;
+
     let mut time = Instant::now();
     let mut dt: f64 = 0.0;
 
@@ -195,21 +346,42 @@ fn main() {
             gl::ClearColor(0.0, 0.0, 0.0, 0.0);
         }
 
+        for err in shader_watcher.poll() {
+            eprintln!("\n[ERR] {}", err);
+        }
+
+        camera_ubo.update(&cam.uniform_block(projection));
+
+        let program = program.borrow();
         program.bind();
-        program.set_mat4("projection", &projection);
-        program.set_mat4("view", &cam.view());
-        program.set_mat4("model", &model);
-        program.set_vec4("eye_pos", &cam.position.to_homogeneous());
-        program.set_vec4("light_pos", &Point3::new(3.0, 1.0, 1.0).to_homogeneous());
+        // Block bindings are program state, set again only once the watcher
+        // relinked the program.
+        if blocks_bound_to != program.addr {
+            program.bind_uniform_buffer("Camera", &camera_ubo).unwrap();
+            program.bind_uniform_buffer("Light", &light_ubo).unwrap();
+            blocks_bound_to = program.addr;
+        }
+        program.set_mat4("model", &model).unwrap();
 
-        cube.draw();
+        if let Err(errors) = cube.draw_with(&program) {
+            for err in errors {
+                eprintln!("\n[ERR] {}", err);
+            }
+        }
 
         gl_window.swap_buffers().unwrap();
 
         let delta = time.elapsed();
         dt = (delta.subsec_micros() as f64) / 1_000_000.0;
         let fps = 1.0 / dt;
-        print!("\r{:.8} ms", dt * 1000.0);
+        let uniforms = program.uniform_stats();
+        program.reset_uniform_stats();
+        print!(
+            "\r{:.8} ms, uniforms {} set {} skipped",
+            dt * 1000.0,
+            uniforms.issued,
+            uniforms.skipped
+        );
         time = Instant::now();
     }
 }
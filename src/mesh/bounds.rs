@@ -0,0 +1,243 @@
+use cgmath::prelude::*;
+use cgmath::{Matrix3, Matrix4, Point3, Vector3};
+
+// Axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+// Sphere containing every point of a mesh, not necessarily the smallest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Aabb {
+        Aabb { min, max }
+    }
+
+    // `None` without points.
+    pub fn from_points<I: IntoIterator<Item = Point3<f32>>>(points: I) -> Option<Aabb> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Aabb::new(first, first), |aabb, point| {
+            aabb.union(&Aabb::new(point, point))
+        }))
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    pub fn size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Point3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Point3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn contains(&self, point: Point3<f32>) -> bool {
+        (0..3).all(|axis| self.min[axis] <= point[axis] && point[axis] <= self.max[axis])
+    }
+
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Point3::new(min.x, min.y, min.z),
+            Point3::new(max.x, min.y, min.z),
+            Point3::new(min.x, max.y, min.z),
+            Point3::new(max.x, max.y, min.z),
+            Point3::new(min.x, min.y, max.z),
+            Point3::new(max.x, min.y, max.z),
+            Point3::new(min.x, max.y, max.z),
+            Point3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    // Box around this one once transformed by the affine `model` matrix. It
+    // grows with rotations, transforming the mesh itself gives a tighter one.
+    pub fn transform(&self, model: &Matrix4<f32>) -> Aabb {
+        let translation = Point3::from_vec(model.w.truncate());
+        let (mut min, mut max) = (translation, translation);
+        // Each output axis is the translation plus the extreme contributions
+        // of every input axis, the column of the matrix scaled by min or max.
+        for column in 0..3 {
+            for row in 0..3 {
+                let a = model[column][row] * self.min[column];
+                let b = model[column][row] * self.max[column];
+                min[row] += a.min(b);
+                max[row] += a.max(b);
+            }
+        }
+        Aabb { min, max }
+    }
+
+    // Sphere through the corners of the box.
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere {
+            center: self.center(),
+            radius: self.size().magnitude() / 2.0,
+        }
+    }
+}
+
+// Largest factor by which the linear part of `model` stretches a vector, its
+// largest singular value: the square root of the largest eigenvalue of
+// `M^T M`, found in closed form since the matrix is symmetric.
+fn max_scale(model: &Matrix4<f32>) -> f32 {
+    let linear = Matrix3::from_cols(model.x.truncate(), model.y.truncate(), model.z.truncate());
+    let m = linear.transpose() * linear;
+    let off_diagonal = m[0][1] * m[0][1] + m[0][2] * m[0][2] + m[1][2] * m[1][2];
+    let diagonal = Vector3::new(m[0][0], m[1][1], m[2][2]);
+    // Squared length of the longest column, a lower bound whatever the
+    // rounding, and the answer without shear.
+    let longest = diagonal.x.max(diagonal.y).max(diagonal.z);
+    if off_diagonal == 0.0 {
+        return longest.sqrt();
+    }
+
+    let q = (diagonal.x + diagonal.y + diagonal.z) / 3.0;
+    let shifted = diagonal - Vector3::new(q, q, q);
+    let p = ((shifted.magnitude2() + 2.0 * off_diagonal) / 6.0).sqrt();
+    let b = (m - Matrix3::from_diagonal(Vector3::new(q, q, q))) / p;
+    let phi = (b.determinant() / 2.0).clamp(-1.0, 1.0).acos() / 3.0;
+    (q + 2.0 * p * phi.cos()).max(longest).sqrt()
+}
+
+impl BoundingSphere {
+    pub fn new(center: Point3<f32>, radius: f32) -> BoundingSphere {
+        BoundingSphere { center, radius }
+    }
+
+    // Centered on the bounding box of the points, tighter than the sphere of
+    // the box since it only reaches the farthest point. `None` without
+    // points.
+    pub fn from_points(points: &[Point3<f32>]) -> Option<BoundingSphere> {
+        let center = Aabb::from_points(points.iter().cloned())?.center();
+        let radius = points
+            .iter()
+            .map(|point| point.distance2(center))
+            .fold(0.0, f32::max)
+            .sqrt();
+        Some(BoundingSphere { center, radius })
+    }
+
+    pub fn contains(&self, point: Point3<f32>) -> bool {
+        point.distance2(self.center) <= self.radius * self.radius
+    }
+
+    // Sphere around this one once transformed by the affine `model` matrix,
+    // scaled by the largest stretch of the matrix. The longest column isn't
+    // enough under shear.
+    pub fn transform(&self, model: &Matrix4<f32>) -> BoundingSphere {
+        BoundingSphere {
+            center: model.transform_point(self.center),
+            radius: self.radius * max_scale(model),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Vector4};
+
+    use crate::mesh::data::MeshData;
+
+    fn assert_close(a: Point3<f32>, b: Point3<f32>) {
+        assert!(a.distance(b) < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn aabb_from_points_and_union() {
+        assert_eq!(Aabb::from_points(Vec::new()), None);
+        let aabb = Aabb::from_points(vec![
+            Point3::new(1.0, -2.0, 0.5),
+            Point3::new(-1.0, 3.0, 0.0),
+            Point3::new(0.0, 0.0, 2.0),
+        ])
+        .unwrap();
+        assert_eq!(aabb.min, Point3::new(-1.0, -2.0, 0.0));
+        assert_eq!(aabb.max, Point3::new(1.0, 3.0, 2.0));
+
+        let other = Aabb::new(Point3::new(0.0, -5.0, 1.0), Point3::new(4.0, 0.0, 1.5));
+        let union = aabb.union(&other);
+        assert_eq!(union.min, Point3::new(-1.0, -5.0, 0.0));
+        assert_eq!(union.max, Point3::new(4.0, 3.0, 2.0));
+        assert!(union.contains(Point3::new(3.0, -4.0, 1.2)));
+        assert!(!aabb.contains(Point3::new(3.0, -4.0, 1.2)));
+    }
+
+    #[test]
+    fn transformed_aabb_bounds_the_transformed_corners() {
+        let aabb = Aabb::new(Point3::new(-1.0, 0.0, -2.0), Point3::new(2.0, 1.0, 1.0));
+        let model = Matrix4::from_translation(Vector3::new(5.0, -1.0, 3.0))
+            * Matrix4::from_angle_y(Deg(45.0));
+
+        let transformed = aabb.transform(&model);
+        let corners = aabb.corners().map(|corner| model.transform_point(corner));
+        let expected = Aabb::from_points(corners.iter().cloned()).unwrap();
+        assert_close(transformed.min, expected.min);
+        assert_close(transformed.max, expected.max);
+    }
+
+    #[test]
+    fn bounds_of_2d_positions_lie_at_z_0() {
+        let mesh = MeshData {
+            vertices: vec![0.0, 0.0, 2.0, 1.0, -1.0, 3.0],
+            v_components: 2,
+            ..MeshData::default()
+        };
+        let aabb = mesh.aabb().unwrap();
+        assert_eq!(aabb.min, Point3::new(-1.0, 0.0, 0.0));
+        assert_eq!(aabb.max, Point3::new(2.0, 3.0, 0.0));
+
+        let sphere = mesh.bounding_sphere().unwrap();
+        assert_eq!(sphere.center, Point3::new(0.5, 1.5, 0.0));
+        assert!((sphere.radius - (1.5f32 * 1.5 + 1.5 * 1.5).sqrt()).abs() < 1e-5);
+    }
+
+    // The columns (1, 0, 0) and (1, 1, 0) are both at most sqrt(2) long, but
+    // the shear stretches some directions by the golden ratio.
+    #[test]
+    fn sheared_sphere_contains_the_sheared_points() {
+        let model = Matrix4::from_cols(
+            Vector4::new(1.0, 0.0, 0.0, 0.0),
+            Vector4::new(1.0, 1.0, 0.0, 0.0),
+            Vector4::new(0.0, 0.0, 1.0, 0.0),
+            Vector4::new(2.0, 0.0, 0.0, 1.0),
+        );
+        let sphere = BoundingSphere::new(Point3::new(0.0, 0.0, 0.0), 1.0);
+        let transformed = sphere.transform(&model);
+        assert_close(transformed.center, Point3::new(2.0, 0.0, 0.0));
+        let golden = (1.0 + 5.0f32.sqrt()) / 2.0;
+        assert!((transformed.radius - golden).abs() < 1e-5);
+
+        for step in 0..360 {
+            let (sin, cos) = (step as f32).to_radians().sin_cos();
+            let point = model.transform_point(Point3::new(cos, sin, 0.0));
+            assert!(point.distance(transformed.center) <= transformed.radius + 1e-5);
+        }
+
+        let rotated = sphere.transform(&Matrix4::from_angle_z(Deg(30.0)));
+        assert!((rotated.radius - 1.0).abs() < 1e-5);
+        let scaled = sphere.transform(&Matrix4::from_nonuniform_scale(1.0, 3.0, 2.0));
+        assert!((scaled.radius - 3.0).abs() < 1e-5);
+    }
+}
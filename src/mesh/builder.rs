@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use super::data::MeshData;
+
+// A face corner. Faces meeting at a hard edge share positions but not
+// normals or texture coordinates, so each corner carries its own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Corner {
+    pub position: [f32; 3],
+    pub normal: Option<[f32; 3]>,
+    pub uv: Option<[f32; 2]>,
+}
+
+impl Corner {
+    pub fn new(position: [f32; 3]) -> Corner {
+        Corner {
+            position,
+            normal: None,
+            uv: None,
+        }
+    }
+
+    pub fn with_normal(mut self, normal: [f32; 3]) -> Corner {
+        self.normal = Some(normal);
+        self
+    }
+
+    pub fn with_uv(mut self, uv: [f32; 2]) -> Corner {
+        self.uv = Some(uv);
+        self
+    }
+}
+
+// Corners are compared bit for bit.
+type CornerKey = ([u32; 3], Option<[u32; 3]>, Option<[u32; 2]>);
+
+fn corner_key(corner: &Corner) -> CornerKey {
+    (
+        [
+            corner.position[0].to_bits(),
+            corner.position[1].to_bits(),
+            corner.position[2].to_bits(),
+        ],
+        corner
+            .normal
+            .map(|n| [n[0].to_bits(), n[1].to_bits(), n[2].to_bits()]),
+        corner.uv.map(|uv| [uv[0].to_bits(), uv[1].to_bits()]),
+    )
+}
+
+// Builds an indexed triangle mesh from faces given corner by corner. Identical
+// corners are welded into one vertex, so every vertex has a single position,
+// normal and texture coordinate and the three attributes share the index
+// buffer. Corners without a normal or texture coordinate get zeros when other
+// corners have one, the attribute is left out when none has.
+#[derive(Debug, Default)]
+pub struct MeshBuilder {
+    corners: Vec<Corner>,
+    welded: HashMap<CornerKey, u32>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    pub fn new() -> MeshBuilder {
+        MeshBuilder::default()
+    }
+
+    // Index of the vertex for `corner`, added unless an identical corner was.
+    pub fn vertex(&mut self, corner: Corner) -> u32 {
+        let corners = &mut self.corners;
+        *self.welded.entry(corner_key(&corner)).or_insert_with(|| {
+            corners.push(corner);
+            (corners.len() - 1) as u32
+        })
+    }
+
+    pub fn triangle(&mut self, a: Corner, b: Corner, c: Corner) {
+        let indices = [self.vertex(a), self.vertex(b), self.vertex(c)];
+        self.indices.extend_from_slice(&indices);
+    }
+
+    // Fan triangulates a convex polygon given counter clockwise.
+    pub fn polygon(&mut self, corners: &[Corner]) {
+        let indices: Vec<u32> = corners.iter().map(|corner| self.vertex(*corner)).collect();
+        for i in 1..indices.len().saturating_sub(1) {
+            self.indices
+                .extend_from_slice(&[indices[0], indices[i], indices[i + 1]]);
+        }
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.corners.len()
+    }
+
+    pub fn index_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn build(self) -> MeshData {
+        let has_normals = self.corners.iter().any(|corner| corner.normal.is_some());
+        let has_uv = self.corners.iter().any(|corner| corner.uv.is_some());
+
+        let mut vertices = Vec::with_capacity(self.corners.len() * 3);
+        let mut normals = Vec::new();
+        let mut uv = Vec::new();
+        for corner in &self.corners {
+            vertices.extend_from_slice(&corner.position);
+            if has_normals {
+                normals.extend_from_slice(&corner.normal.unwrap_or([0.0; 3]));
+            }
+            if has_uv {
+                uv.extend_from_slice(&corner.uv.unwrap_or([0.0; 2]));
+            }
+        }
+
+        MeshData {
+            vertices,
+            indices: Some(self.indices),
+            normals: if has_normals { Some(normals) } else { None },
+            uv: if has_uv { Some(uv) } else { None },
+            v_components: 3,
+            n_components: if has_normals { 3 } else { 0 },
+            uv_components: if has_uv { 2 } else { 0 },
+            ..MeshData::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cube_welds_to_24_vertices() {
+        let cube = MeshData::cube();
+        cube.check().unwrap();
+        assert_eq!(cube.vertex_count(), 24);
+        assert_eq!(cube.element_count(), 36);
+    }
+
+    #[test]
+    fn identical_corners_weld() {
+        let corner = Corner::new([1.0, 2.0, 3.0]).with_uv([0.5, 0.5]);
+        let mut builder = MeshBuilder::new();
+        let first = builder.vertex(corner);
+        assert_eq!(builder.vertex(corner), first);
+        assert_ne!(builder.vertex(corner.with_uv([0.0, 0.5])), first);
+        assert_ne!(builder.vertex(corner.with_normal([0.0, 1.0, 0.0])), first);
+        assert_eq!(builder.vertex_count(), 3);
+    }
+}
@@ -0,0 +1,572 @@
+use std::error::Error;
+use std::fmt;
+
+use cgmath::{EuclideanSpace, Point3, Vector3};
+
+use gl;
+
+use crate::shaders::Program;
+
+use super::bounds::{Aabb, BoundingSphere};
+use super::builder::{Corner, MeshBuilder};
+use super::layout::{AttributeFormat, VertexBuffer, VertexLayout};
+use super::{validate_attributes, AttributeError, VertexAttribute};
+
+// Geometry and attributes of a mesh, without GL resources: meshes are built,
+// loaded and processed as `MeshData`, then uploaded to a `GpuMesh`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshData {
+    pub vertices: Vec<f32>,
+    pub indices: Option<Vec<u32>>,
+    pub normals: Option<Vec<f32>>,
+    pub uv: Option<Vec<f32>>,
+    // Four components: the tangent and the handedness of the bitangent, see
+    // `generate_tangents`.
+    pub tangents: Option<Vec<f32>>,
+    // When set, replaces the attribute arrays with a single buffer
+    // holding any attributes.
+    pub interleaved: Option<VertexBuffer>,
+
+    pub v_components: i32,
+    pub n_components: i32,
+    pub uv_components: i32,
+    pub draw_type: u32,
+    pub patch_vertices: i32,
+}
+
+impl Default for MeshData {
+    fn default() -> MeshData {
+        MeshData {
+            vertices: Vec::new(),
+            indices: None,
+            normals: None,
+            uv: None,
+            tangents: None,
+            interleaved: None,
+            v_components: 3,
+            n_components: 0,
+            uv_components: 0,
+            draw_type: gl::TRIANGLES,
+            patch_vertices: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MeshError {
+    Components {
+        name: String,
+        components: i32,
+    },
+    Length {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    Index {
+        index: u32,
+        vertices: usize,
+    },
+    // An attribute needed by some processing.
+    Missing(String),
+    // Processing that only handles triangle lists.
+    DrawType(u32),
+    // Processing that only handles separate attribute arrays.
+    Interleaved,
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MeshError::Components { name, components } => write!(
+                f,
+                "attribute {} has {} components, expected 1 to 4",
+                name, components
+            ),
+            MeshError::Length {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "attribute {} has {} values, expected {}",
+                name, found, expected
+            ),
+            MeshError::Index { index, vertices } => write!(
+                f,
+                "index {} out of range, the mesh has {} vertices",
+                index, vertices
+            ),
+            MeshError::Missing(name) => write!(f, "the mesh has no {} attribute", name),
+            MeshError::DrawType(draw_type) => {
+                write!(f, "draw type {:#x} isn't a triangle list", draw_type)
+            }
+            MeshError::Interleaved => write!(f, "the mesh is interleaved"),
+        }
+    }
+}
+
+impl Error for MeshError {}
+
+impl MeshData {
+    pub fn vertex_count(&self) -> usize {
+        match &self.interleaved {
+            Some(buffer) => buffer.vertex_count(),
+            None if self.v_components > 0 => self.vertices.len() / self.v_components as usize,
+            None => 0,
+        }
+    }
+
+    // Number of vertices drawn: indices, or vertices without index buffer.
+    pub fn element_count(&self) -> usize {
+        self.indices
+            .as_ref()
+            .map_or_else(|| self.vertex_count(), Vec::len)
+    }
+
+    // Checks that the attributes hold the same number of vertices and that
+    // the indices refer to them.
+    pub fn check(&self) -> Result<(), MeshError> {
+        let vertices = self.vertex_count();
+
+        match &self.interleaved {
+            Some(buffer) => {
+                for attribute in &buffer.layout.attributes {
+                    if attribute.components < 1 || attribute.components > 4 {
+                        return Err(MeshError::Components {
+                            name: attribute.name.clone(),
+                            components: attribute.components,
+                        });
+                    }
+                }
+                if buffer.data.len() != vertices * buffer.layout.stride {
+                    return Err(MeshError::Length {
+                        name: "interleaved".to_string(),
+                        expected: vertices * buffer.layout.stride,
+                        found: buffer.data.len(),
+                    });
+                }
+            }
+            None => {
+                let attributes = [
+                    ("position", Some(&self.vertices), self.v_components),
+                    ("normal", self.normals.as_ref(), self.n_components),
+                    ("uv", self.uv.as_ref(), self.uv_components),
+                    ("tangent", self.tangents.as_ref(), 4),
+                ];
+                for (name, values, components) in attributes.iter() {
+                    let values = match values {
+                        Some(values) => values,
+                        None => continue,
+                    };
+                    if *components < 1 || *components > 4 {
+                        return Err(MeshError::Components {
+                            name: name.to_string(),
+                            components: *components,
+                        });
+                    }
+                    let expected = vertices * *components as usize;
+                    if values.len() != expected {
+                        return Err(MeshError::Length {
+                            name: name.to_string(),
+                            expected,
+                            found: values.len(),
+                        });
+                    }
+                }
+            }
+        }
+
+        let indices = self.indices.iter().flatten();
+        if let Some(index) = indices.cloned().find(|index| *index as usize >= vertices) {
+            return Err(MeshError::Index { index, vertices });
+        }
+        Ok(())
+    }
+
+    pub fn vertex_attributes(&self) -> Vec<VertexAttribute> {
+        if let Some(buffer) = &self.interleaved {
+            return buffer
+                .layout
+                .attributes
+                .iter()
+                .map(|attribute| VertexAttribute {
+                    location: attribute.location,
+                    components: attribute.components,
+                    integer: attribute.format.is_integer(),
+                })
+                .collect();
+        }
+
+        let mut attributes = vec![VertexAttribute {
+            location: 0,
+            components: self.v_components,
+            integer: false,
+        }];
+        if self.normals.is_some() {
+            attributes.push(VertexAttribute {
+                location: 1,
+                components: self.n_components,
+                integer: false,
+            });
+        }
+        if self.uv.is_some() {
+            attributes.push(VertexAttribute {
+                location: 2,
+                components: self.uv_components,
+                integer: false,
+            });
+        }
+        if self.tangents.is_some() {
+            attributes.push(VertexAttribute {
+                location: 3,
+                components: 4,
+                integer: false,
+            });
+        }
+        attributes
+    }
+
+    // Checks that the mesh feeds every vertex input of `program`, see
+    // `GpuMesh::validate`.
+    pub fn validate(&self, program: &Program) -> Result<(), Vec<AttributeError>> {
+        validate_attributes(&self.vertex_attributes(), &program.attributes)
+    }
+
+    // Draws the mesh as patches of `vertices` control points, to be fed to
+    // the tessellation stages.
+    pub fn set_patches(&mut self, vertices: i32) {
+        self.draw_type = gl::PATCHES;
+        self.patch_vertices = vertices;
+    }
+
+    // Positions as 3D points, the missing components being zeros. The
+    // position of interleaved meshes is the attribute at location 0.
+    pub fn positions(&self) -> Vec<Vector3<f32>> {
+        let point = |values: &[f32]| {
+            let component = |index: usize| values.get(index).cloned().unwrap_or(0.0);
+            Vector3::new(component(0), component(1), component(2))
+        };
+
+        match &self.interleaved {
+            Some(buffer) => {
+                let attribute = buffer
+                    .layout
+                    .attributes
+                    .iter()
+                    .find(|attribute| attribute.location == 0);
+                match attribute {
+                    Some(attribute) => (0..buffer.vertex_count())
+                        .map(|vertex| point(&buffer.read(vertex, attribute)))
+                        .collect(),
+                    None => Vec::new(),
+                }
+            }
+            None if self.v_components > 0 => self
+                .vertices
+                .chunks(self.v_components as usize)
+                .map(point)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Box around the positions, 2D positions lying at z = 0. `None` without
+    // vertices.
+    pub fn aabb(&self) -> Option<Aabb> {
+        Aabb::from_points(self.positions().into_iter().map(Point3::from_vec))
+    }
+
+    pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        let points: Vec<Point3<f32>> = self.positions().into_iter().map(Point3::from_vec).collect();
+        BoundingSphere::from_points(&points)
+    }
+
+    // Vertex indices of each triangle of a triangle list, indexed or not.
+    pub fn triangles(&self) -> Result<Vec<[u32; 3]>, MeshError> {
+        if self.draw_type != gl::TRIANGLES {
+            return Err(MeshError::DrawType(self.draw_type));
+        }
+        Ok(match &self.indices {
+            Some(indices) => indices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+            None => (0..self.vertex_count() as u32 / 3)
+                .map(|t| [3 * t, 3 * t + 1, 3 * t + 2])
+                .collect(),
+        })
+    }
+
+    // Replaces the vertices by copies of `sources`, e.g. to split vertices
+    // whose corners need different attributes, and the indices by `indices`
+    // into the new vertices.
+    pub(super) fn remap_vertices(&mut self, sources: &[u32], indices: Vec<u32>) {
+        let copy = |values: &[f32], components: i32| -> Vec<f32> {
+            let components = components as usize;
+            sources
+                .iter()
+                .flat_map(|source| {
+                    let start = *source as usize * components;
+                    values[start..start + components].iter().cloned()
+                })
+                .collect()
+        };
+
+        self.vertices = copy(&self.vertices, self.v_components);
+        self.normals = self.normals.as_ref().map(|n| copy(n, self.n_components));
+        self.uv = self.uv.as_ref().map(|uv| copy(uv, self.uv_components));
+        self.tangents = self.tangents.as_ref().map(|t| copy(t, 4));
+        self.indices = Some(indices);
+    }
+
+    pub fn from_vertex_buffer(buffer: VertexBuffer, indices: Option<Vec<u32>>) -> MeshData {
+        MeshData {
+            indices,
+            interleaved: Some(buffer),
+            v_components: 0,
+            ..MeshData::default()
+        }
+    }
+
+    // Copy of the mesh with its attributes interleaved in one buffer, see
+    // `VertexLayout::standard`. Tangents follow at location 3.
+    pub fn interleave(&self) -> MeshData {
+        let buffer = match &self.interleaved {
+            Some(buffer) => buffer.clone(),
+            None => {
+                let mut attributes = vec![(self.vertices.as_slice(), self.v_components)];
+                if let Some(normals) = &self.normals {
+                    attributes.push((normals, self.n_components));
+                }
+                if let Some(uv) = &self.uv {
+                    attributes.push((uv, self.uv_components));
+                }
+                if let Some(tangents) = &self.tangents {
+                    attributes.push((tangents, 4));
+                }
+                let attributes: Vec<_> = attributes
+                    .into_iter()
+                    .filter(|(_, components)| *components > 0)
+                    .map(|(values, components)| (values, components as usize))
+                    .collect();
+
+                let n_components = self.normals.as_ref().map_or(0, |_| self.n_components);
+                let uv_components = self.uv.as_ref().map_or(0, |_| self.uv_components);
+                let mut layout =
+                    VertexLayout::standard(self.v_components, n_components, uv_components);
+                if self.tangents.is_some() {
+                    layout = layout.with_attribute("tangent", 3, 4, AttributeFormat::F32);
+                }
+                let mut buffer = VertexBuffer::new(layout);
+                for vertex in 0..self.vertex_count() {
+                    let values: Vec<&[f32]> = attributes
+                        .iter()
+                        .map(|(values, components)| {
+                            values
+                                .get(vertex * components..(vertex + 1) * components)
+                                .unwrap_or(&[])
+                        })
+                        .collect();
+                    buffer.push_vertex(&values);
+                }
+                buffer
+            }
+        };
+
+        MeshData {
+            draw_type: self.draw_type,
+            patch_vertices: self.patch_vertices,
+            ..MeshData::from_vertex_buffer(buffer, self.indices.clone())
+        }
+    }
+
+    // Cube from -1 to 1 on each axis with a normal per face, laid out like
+    // `data/cube2.obj`.
+    pub fn cube() -> MeshData {
+        const POSITIONS: [[f32; 3]; 8] = [
+            [-1.0, -1.0, 1.0],
+            [-1.0, 1.0, 1.0],
+            [-1.0, -1.0, -1.0],
+            [-1.0, 1.0, -1.0],
+            [1.0, -1.0, 1.0],
+            [1.0, 1.0, 1.0],
+            [1.0, -1.0, -1.0],
+            [1.0, 1.0, -1.0],
+        ];
+        const NORMALS: [[f32; 3]; 6] = [
+            [-1.0, 0.0, 0.0],
+            [0.0, 0.0, -1.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [0.0, -1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        const UV: [[f32; 2]; 24] = [
+            [0.000200, 0.666866],
+            [0.333134, 0.999800],
+            [0.000200, 0.999800],
+            [0.666866, 0.000200],
+            [0.999800, 0.333134],
+            [0.666866, 0.333134],
+            [0.333134, 0.666467],
+            [0.000200, 0.333533],
+            [0.333134, 0.333533],
+            [0.666467, 0.666467],
+            [0.333533, 0.333533],
+            [0.666467, 0.333533],
+            [0.333533, 0.333134],
+            [0.666467, 0.000200],
+            [0.666467, 0.333134],
+            [0.000200, 0.333134],
+            [0.333134, 0.000200],
+            [0.333134, 0.333134],
+            [0.333134, 0.666866],
+            [0.999800, 0.000200],
+            [0.000200, 0.666467],
+            [0.333533, 0.666467],
+            [0.333533, 0.000200],
+            [0.000200, 0.000200],
+        ];
+        // Two triangles per face, corners as (position, uv) with the face
+        // normal.
+        const FACES: [[(usize, usize); 6]; 6] = [
+            [(1, 0), (2, 1), (0, 2), (1, 0), (3, 18), (2, 1)],
+            [(3, 3), (6, 4), (2, 5), (3, 3), (7, 19), (6, 4)],
+            [(7, 6), (4, 7), (6, 8), (7, 6), (5, 20), (4, 7)],
+            [(5, 9), (0, 10), (4, 11), (5, 9), (1, 21), (0, 10)],
+            [(6, 12), (0, 13), (2, 14), (6, 12), (4, 22), (0, 13)],
+            [(3, 15), (5, 16), (7, 17), (3, 15), (1, 23), (5, 16)],
+        ];
+
+        let mut builder = MeshBuilder::new();
+        for (normal, face) in NORMALS.iter().zip(FACES.iter()) {
+            let corners: Vec<Corner> = face
+                .iter()
+                .map(|&(position, uv)| {
+                    Corner::new(POSITIONS[position])
+                        .with_normal(*normal)
+                        .with_uv(UV[uv])
+                })
+                .collect();
+            for triangle in corners.chunks(3) {
+                builder.triangle(triangle[0], triangle[1], triangle[2]);
+            }
+        }
+        builder.build()
+    }
+
+    pub fn fs_quad() -> MeshData {
+        MeshData {
+            v_components: 2,
+            vertices: vec![
+                -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 1.0,
+            ],
+            uv_components: 2,
+            uv: Some(vec![
+                0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 1.0,
+            ]),
+            ..MeshData::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> MeshData {
+        MeshData {
+            vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+            normals: Some(vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0]),
+            uv: Some(vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0]),
+            n_components: 3,
+            uv_components: 2,
+            ..MeshData::default()
+        }
+    }
+
+    #[test]
+    fn check_reports_lengths() {
+        let mut mesh = triangle();
+        mesh.uv.as_mut().unwrap().pop();
+        assert_eq!(
+            mesh.check(),
+            Err(MeshError::Length {
+                name: "uv".to_string(),
+                expected: 6,
+                found: 5,
+            })
+        );
+    }
+
+    #[test]
+    fn check_reports_components() {
+        let mut mesh = triangle();
+        mesh.n_components = 5;
+        assert_eq!(
+            mesh.check(),
+            Err(MeshError::Components {
+                name: "normal".to_string(),
+                components: 5,
+            })
+        );
+    }
+
+    #[test]
+    fn check_reports_indices() {
+        let mut mesh = triangle();
+        mesh.indices = Some(vec![0, 1, 3]);
+        assert_eq!(
+            mesh.check(),
+            Err(MeshError::Index {
+                index: 3,
+                vertices: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn triangles_of_indexed_and_plain_meshes() {
+        let mut mesh = triangle();
+        assert_eq!(mesh.triangles(), Ok(vec![[0, 1, 2]]));
+        assert_eq!(mesh.element_count(), 3);
+
+        mesh.indices = Some(vec![0, 1, 2, 2, 1, 0]);
+        assert_eq!(mesh.triangles(), Ok(vec![[0, 1, 2], [2, 1, 0]]));
+        assert_eq!(mesh.element_count(), 6);
+
+        mesh.draw_type = gl::LINES;
+        assert_eq!(mesh.triangles(), Err(MeshError::DrawType(gl::LINES)));
+    }
+
+    #[test]
+    fn interleave_round_trips() {
+        let mesh = MeshData {
+            indices: Some(vec![2, 1, 0]),
+            ..triangle()
+        };
+        let interleaved = mesh.interleave();
+        interleaved.check().unwrap();
+        assert_eq!(interleaved.vertex_count(), 3);
+        assert_eq!(interleaved.element_count(), 3);
+        assert_eq!(interleaved.vertex_attributes(), mesh.vertex_attributes());
+
+        let buffer = interleaved.interleaved.as_ref().unwrap();
+        let attributes = [
+            ("position", &mesh.vertices, 3),
+            ("normal", mesh.normals.as_ref().unwrap(), 3),
+            ("uv", mesh.uv.as_ref().unwrap(), 2),
+        ];
+        for (name, values, components) in attributes.iter() {
+            let attribute = buffer.layout.attribute(name).unwrap();
+            for vertex in 0..3 {
+                assert_eq!(
+                    buffer.read(vertex, attribute),
+                    &values[vertex * components..(vertex + 1) * components]
+                );
+            }
+        }
+        assert_eq!(interleaved.positions(), mesh.positions());
+    }
+}
@@ -0,0 +1,211 @@
+use std::ops::Range;
+use std::os::raw::c_void;
+
+use gl;
+
+use crate::shaders::Program;
+
+use super::data::MeshData;
+use super::layout::VertexBuffer;
+use super::{validate_attributes, AttributeError, VertexAttribute};
+
+// Buffers and vertex array of an uploaded `MeshData`, deleted on drop. The
+// data can be dropped once uploaded.
+#[derive(Debug)]
+pub struct GpuMesh {
+    pub vao: u32,
+    pub vertex_buffers: Vec<u32>,
+    pub index_buffer: Option<u32>,
+    // Number of indices, or vertices when there is no index buffer.
+    pub count: i32,
+    pub draw_type: u32,
+    pub patch_vertices: i32,
+    pub attributes: Vec<VertexAttribute>,
+}
+
+fn gen_buffer<T>(target: u32, data: &[T]) -> u32 {
+    let mut addr: u32 = 0;
+    unsafe {
+        gl::GenBuffers(1, &mut addr);
+        gl::BindBuffer(target, addr);
+        gl::BufferData(
+            target,
+            std::mem::size_of_val(data) as isize,
+            data.as_ptr() as *const c_void,
+            gl::STATIC_DRAW,
+        );
+    }
+    addr
+}
+
+// Separate, tightly packed float buffer at `location`.
+fn float_attribute(location: u32, components: i32, values: &[f32]) -> u32 {
+    let addr = gen_buffer(gl::ARRAY_BUFFER, values);
+    unsafe {
+        gl::EnableVertexAttribArray(location);
+        gl::VertexAttribPointer(
+            location,
+            components,
+            gl::FLOAT,
+            gl::FALSE,
+            0,
+            std::ptr::null_mut(),
+        );
+    }
+    addr
+}
+
+fn interleaved_attributes(buffer: &VertexBuffer) -> u32 {
+    let addr = gen_buffer(gl::ARRAY_BUFFER, &buffer.data);
+    let stride = buffer.layout.stride as i32;
+    for attribute in &buffer.layout.attributes {
+        let offset = attribute.offset as *const c_void;
+        unsafe {
+            gl::EnableVertexAttribArray(attribute.location);
+            if attribute.format.is_integer() {
+                gl::VertexAttribIPointer(
+                    attribute.location,
+                    attribute.components,
+                    attribute.format.gl_type(),
+                    stride,
+                    offset,
+                );
+            } else {
+                gl::VertexAttribPointer(
+                    attribute.location,
+                    attribute.components,
+                    attribute.format.gl_type(),
+                    attribute.format.is_normalized() as u8,
+                    stride,
+                    offset,
+                );
+            }
+        }
+    }
+    addr
+}
+
+impl MeshData {
+    // Positions, normals, texture coordinates and tangents are read at
+    // locations 0 to 3, interleaved meshes use the locations of their layout.
+    pub fn upload(&self) -> GpuMesh {
+        let mut vao: u32 = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::BindVertexArray(vao);
+        }
+
+        let mut vertex_buffers = Vec::new();
+        match &self.interleaved {
+            Some(buffer) => vertex_buffers.push(interleaved_attributes(buffer)),
+            None => {
+                vertex_buffers.push(float_attribute(0, self.v_components, &self.vertices));
+                if let Some(normals) = &self.normals {
+                    vertex_buffers.push(float_attribute(1, self.n_components, normals));
+                }
+                if let Some(uv) = &self.uv {
+                    vertex_buffers.push(float_attribute(2, self.uv_components, uv));
+                }
+                if let Some(tangents) = &self.tangents {
+                    vertex_buffers.push(float_attribute(3, 4, tangents));
+                }
+            }
+        }
+
+        // The index buffer binding is part of the vertex array state, so the
+        // vertex array is unbound before it.
+        let index_buffer = self
+            .indices
+            .as_ref()
+            .map(|indices| gen_buffer(gl::ELEMENT_ARRAY_BUFFER, indices));
+
+        unsafe {
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+        }
+
+        GpuMesh {
+            vao,
+            vertex_buffers,
+            index_buffer,
+            count: self.element_count() as i32,
+            draw_type: self.draw_type,
+            patch_vertices: self.patch_vertices,
+            attributes: self.vertex_attributes(),
+        }
+    }
+}
+
+impl Drop for GpuMesh {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(
+                self.vertex_buffers.len() as i32,
+                self.vertex_buffers.as_ptr(),
+            );
+            if let Some(index_buffer) = self.index_buffer {
+                gl::DeleteBuffers(1, &index_buffer);
+            }
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+impl GpuMesh {
+    // Checks that the mesh feeds every vertex input of `program` with enough
+    // components of the right kind. Providing more components than used is
+    // fine.
+    pub fn validate(&self, program: &Program) -> Result<(), Vec<AttributeError>> {
+        validate_attributes(&self.attributes, &program.attributes)
+    }
+
+    pub fn draw_with(&self, program: &Program) -> Result<(), Vec<AttributeError>> {
+        self.validate(program)?;
+        self.draw();
+        Ok(())
+    }
+
+    pub fn draw(&self) {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            if self.draw_type == gl::PATCHES {
+                gl::PatchParameteri(gl::PATCH_VERTICES, self.patch_vertices);
+            }
+
+            if self.index_buffer.is_some() {
+                gl::DrawElements(
+                    self.draw_type,
+                    self.count,
+                    gl::UNSIGNED_INT,
+                    std::ptr::null_mut(),
+                );
+            } else {
+                gl::DrawArrays(self.draw_type, 0, self.count);
+            }
+            gl::BindVertexArray(0);
+        }
+    }
+
+    // Draws part of the index buffer, e.g. one `ObjGroup`. Does nothing for
+    // meshes without indices, and stops at the end of the buffer.
+    pub fn draw_range(&self, range: Range<usize>) {
+        let range = range.start..range.end.min(self.count as usize);
+        if self.index_buffer.is_none() || range.is_empty() {
+            return;
+        }
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            if self.draw_type == gl::PATCHES {
+                gl::PatchParameteri(gl::PATCH_VERTICES, self.patch_vertices);
+            }
+            gl::DrawElements(
+                self.draw_type,
+                range.len() as i32,
+                gl::UNSIGNED_INT,
+                (range.start * std::mem::size_of::<u32>()) as *const c_void,
+            );
+            gl::BindVertexArray(0);
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use gl;
+
+// Storage of an attribute in a vertex buffer. Values are given as f32 and
+// converted when written: `*Norm` formats are read by shaders as floats in
+// [0, 1] (unsigned) or [-1, 1] (signed), `U8` to `I32` feed integer inputs
+// (`ivec*`, `uvec*`) rounded to the nearest integer, saturating at the bounds
+// of the type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeFormat {
+    F32,
+    F16,
+    U8Norm,
+    U16Norm,
+    I8Norm,
+    I16Norm,
+    U8,
+    U16,
+    U32,
+    I32,
+}
+
+impl AttributeFormat {
+    pub fn size(self) -> usize {
+        match self {
+            AttributeFormat::U8 | AttributeFormat::U8Norm | AttributeFormat::I8Norm => 1,
+            AttributeFormat::F16
+            | AttributeFormat::U16
+            | AttributeFormat::U16Norm
+            | AttributeFormat::I16Norm => 2,
+            AttributeFormat::F32 | AttributeFormat::U32 | AttributeFormat::I32 => 4,
+        }
+    }
+
+    pub fn gl_type(self) -> u32 {
+        match self {
+            AttributeFormat::F32 => gl::FLOAT,
+            AttributeFormat::F16 => gl::HALF_FLOAT,
+            AttributeFormat::U8 | AttributeFormat::U8Norm => gl::UNSIGNED_BYTE,
+            AttributeFormat::U16 | AttributeFormat::U16Norm => gl::UNSIGNED_SHORT,
+            AttributeFormat::I8Norm => gl::BYTE,
+            AttributeFormat::I16Norm => gl::SHORT,
+            AttributeFormat::U32 => gl::UNSIGNED_INT,
+            AttributeFormat::I32 => gl::INT,
+        }
+    }
+
+    pub fn is_normalized(self) -> bool {
+        matches!(
+            self,
+            AttributeFormat::U8Norm
+                | AttributeFormat::U16Norm
+                | AttributeFormat::I8Norm
+                | AttributeFormat::I16Norm
+        )
+    }
+
+    pub fn is_integer(self) -> bool {
+        matches!(
+            self,
+            AttributeFormat::U8
+                | AttributeFormat::U16
+                | AttributeFormat::U32
+                | AttributeFormat::I32
+        )
+    }
+
+    fn write(self, value: f32, data: &mut [u8]) {
+        match self {
+            AttributeFormat::F32 => data.copy_from_slice(&value.to_ne_bytes()),
+            AttributeFormat::F16 => data.copy_from_slice(&f32_to_f16(value).to_ne_bytes()),
+            AttributeFormat::U8Norm => data[0] = (value.clamp(0.0, 1.0) * 255.0).round() as u8,
+            AttributeFormat::U16Norm => data
+                .copy_from_slice(&((value.clamp(0.0, 1.0) * 65535.0).round() as u16).to_ne_bytes()),
+            AttributeFormat::I8Norm => {
+                data[0] = (value.clamp(-1.0, 1.0) * 127.0).round() as i8 as u8
+            }
+            AttributeFormat::I16Norm => data.copy_from_slice(
+                &((value.clamp(-1.0, 1.0) * 32767.0).round() as i16).to_ne_bytes(),
+            ),
+            AttributeFormat::U8 => data[0] = value.round() as u8,
+            AttributeFormat::U16 => data.copy_from_slice(&(value.round() as u16).to_ne_bytes()),
+            AttributeFormat::U32 => data.copy_from_slice(&(value.round() as u32).to_ne_bytes()),
+            AttributeFormat::I32 => data.copy_from_slice(&(value.round() as i32).to_ne_bytes()),
+        }
+    }
+
+    fn read(self, data: &[u8]) -> f32 {
+        let mut bytes = [0; 4];
+        bytes[..data.len()].copy_from_slice(data);
+        let [b0, b1, b2, b3] = bytes;
+        match self {
+            AttributeFormat::F32 => f32::from_ne_bytes(bytes),
+            AttributeFormat::F16 => f16_to_f32(u16::from_ne_bytes([b0, b1])),
+            AttributeFormat::U8Norm => f32::from(b0) / 255.0,
+            AttributeFormat::U16Norm => f32::from(u16::from_ne_bytes([b0, b1])) / 65535.0,
+            AttributeFormat::I8Norm => (f32::from(b0 as i8) / 127.0).max(-1.0),
+            AttributeFormat::I16Norm => {
+                (f32::from(i16::from_ne_bytes([b0, b1])) / 32767.0).max(-1.0)
+            }
+            AttributeFormat::U8 => f32::from(b0),
+            AttributeFormat::U16 => f32::from(u16::from_ne_bytes([b0, b1])),
+            AttributeFormat::U32 => u32::from_ne_bytes([b0, b1, b2, b3]) as f32,
+            AttributeFormat::I32 => i32::from_ne_bytes([b0, b1, b2, b3]) as f32,
+        }
+    }
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = u32::from(half & 0x8000) << 16;
+    let exponent = u32::from(half >> 10) & 0x1f;
+    let mantissa = u32::from(half & 0x03ff);
+
+    let bits = match exponent {
+        0 if mantissa == 0 => sign,
+        // Subnormal, exactly representable as a normal f32.
+        0 => {
+            let value = mantissa as f32 / (1 << 24) as f32;
+            return if sign != 0 { -value } else { value };
+        }
+        0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+// Drops the `shift` low bits of `value`, rounding to nearest, ties to even.
+fn shift_round(value: u32, shift: u32) -> u32 {
+    let truncated = value >> shift;
+    let remainder = value & ((1 << shift) - 1);
+    let half = 1 << (shift - 1);
+    if remainder > half || (remainder == half && truncated & 1 == 1) {
+        truncated + 1
+    } else {
+        truncated
+    }
+}
+
+// Rounds to the nearest half float, ties to even, overflowing to infinity.
+// A rounding carry out of the mantissa moves to the next exponent, up to
+// infinity.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // Subnormal: the implicit leading one becomes explicit.
+        let mantissa = mantissa | 0x0080_0000;
+        return sign | shift_round(mantissa, (14 - exponent) as u32) as u16;
+    }
+
+    sign | (((exponent as u32) << 10) + shift_round(mantissa, 13)) as u16
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutAttribute {
+    pub name: String,
+    pub location: u32,
+    pub components: i32,
+    pub format: AttributeFormat,
+    // In bytes from the start of the vertex.
+    pub offset: usize,
+}
+
+impl LayoutAttribute {
+    pub fn size(&self) -> usize {
+        self.components as usize * self.format.size()
+    }
+}
+
+// Attributes of the vertices of an interleaved buffer, each vertex taking
+// `stride` bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VertexLayout {
+    pub attributes: Vec<LayoutAttribute>,
+    pub stride: usize,
+}
+
+impl VertexLayout {
+    pub fn new() -> VertexLayout {
+        VertexLayout::default()
+    }
+
+    // Position, normal and texture coordinates at locations 0, 1 and 2 like
+    // the separate buffers of `MeshData`. Attributes with 0 components are left
+    // out.
+    pub fn standard(v_components: i32, n_components: i32, uv_components: i32) -> VertexLayout {
+        let attributes = [
+            ("position", 0, v_components),
+            ("normal", 1, n_components),
+            ("uv", 2, uv_components),
+        ];
+        attributes
+            .iter()
+            .filter(|(_, _, components)| *components > 0)
+            .fold(
+                VertexLayout::new(),
+                |layout, (name, location, components)| {
+                    layout.with_attribute(name, *location, *components, AttributeFormat::F32)
+                },
+            )
+    }
+
+    // Appends an attribute after the others, 4 bytes aligned.
+    pub fn with_attribute(
+        self,
+        name: &str,
+        location: u32,
+        components: i32,
+        format: AttributeFormat,
+    ) -> VertexLayout {
+        let offset = self.stride.next_multiple_of(4);
+        self.with_attribute_at(name, location, components, format, offset)
+    }
+
+    // Places an attribute at `offset`, growing the stride to hold it.
+    pub fn with_attribute_at(
+        mut self,
+        name: &str,
+        location: u32,
+        components: i32,
+        format: AttributeFormat,
+        offset: usize,
+    ) -> VertexLayout {
+        let attribute = LayoutAttribute {
+            name: name.to_string(),
+            location,
+            components,
+            format,
+            offset,
+        };
+        self.stride = self
+            .stride
+            .max((offset + attribute.size()).next_multiple_of(4));
+        self.attributes.push(attribute);
+        self
+    }
+
+    // Overrides the stride, e.g. to leave room for data not read by shaders.
+    pub fn with_stride(mut self, stride: usize) -> VertexLayout {
+        self.stride = stride;
+        self
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&LayoutAttribute> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.name == name)
+    }
+}
+
+// Vertices stored in a single buffer as described by `layout`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VertexBuffer {
+    pub layout: VertexLayout,
+    pub data: Vec<u8>,
+}
+
+impl VertexBuffer {
+    pub fn new(layout: VertexLayout) -> VertexBuffer {
+        VertexBuffer {
+            layout,
+            data: Vec::new(),
+        }
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.data.len().checked_div(self.layout.stride).unwrap_or(0)
+    }
+
+    // Components of `attribute` for `vertex`, converted back to f32.
+    pub fn read(&self, vertex: usize, attribute: &LayoutAttribute) -> Vec<f32> {
+        let size = attribute.format.size();
+        let start = vertex * self.layout.stride + attribute.offset;
+        (0..attribute.components as usize)
+            .map(|component| {
+                let offset = start + component * size;
+                attribute.format.read(&self.data[offset..offset + size])
+            })
+            .collect()
+    }
+
+    // Appends a vertex, `values` holding the components of each attribute in
+    // layout order. Missing attributes or components are zeros, extra ones
+    // are ignored.
+    pub fn push_vertex(&mut self, values: &[&[f32]]) {
+        let start = self.data.len();
+        self.data.resize(start + self.layout.stride, 0);
+        for (index, attribute) in self.layout.attributes.iter().enumerate() {
+            let values = values.get(index).cloned().unwrap_or(&[]);
+            let size = attribute.format.size();
+            for (component, value) in values
+                .iter()
+                .take(attribute.components as usize)
+                .enumerate()
+            {
+                let offset = start + attribute.offset + component * size;
+                attribute
+                    .format
+                    .write(*value, &mut self.data[offset..offset + size]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_half_round_trips() {
+        for half in 0..=u16::MAX {
+            let value = f16_to_f32(half);
+            if value.is_nan() {
+                assert!(f16_to_f32(f32_to_f16(value)).is_nan());
+            } else {
+                assert_eq!(f32_to_f16(value), half, "{:#06x} ({})", half, value);
+            }
+        }
+    }
+
+    #[test]
+    fn half_edge_cases() {
+        let min_subnormal = 2.0f32.powi(-24);
+        assert_eq!(f32_to_f16(min_subnormal), 0x0001);
+        assert_eq!(f32_to_f16(1023.0 * min_subnormal), 0x03ff);
+        // Ties to even: half the smallest subnormal goes to 0, 1.5 of it to 2.
+        assert_eq!(f32_to_f16(min_subnormal / 2.0), 0x0000);
+        assert_eq!(f32_to_f16(min_subnormal * 1.5), 0x0002);
+        assert_eq!(f32_to_f16(-min_subnormal / 4.0), 0x8000);
+
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16(65519.0), 0x7bff);
+        assert_eq!(f32_to_f16(65520.0), 0x7c00);
+        assert_eq!(f32_to_f16(1e10), 0x7c00);
+        assert_eq!(f32_to_f16(f32::NEG_INFINITY), 0xfc00);
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+
+        // The largest mantissa rounding up carries into the exponent.
+        assert_eq!(f32_to_f16(2.0 - 2.0f32.powi(-11)), 0x4000);
+        assert_eq!(f32_to_f16(1.0 + 2.0f32.powi(-11)), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 * 2.0f32.powi(-11)), 0x3c02);
+        // The largest subnormal rounding up becomes the smallest normal.
+        assert_eq!(f32_to_f16(1023.75 * min_subnormal), 0x0400);
+    }
+
+    #[test]
+    fn integers_are_rounded() {
+        let mut data = [0; 4];
+        AttributeFormat::I32.write(-2.6, &mut data);
+        assert_eq!(i32::from_ne_bytes(data), -3);
+        AttributeFormat::U8.write(2.5, &mut data[..1]);
+        assert_eq!(data[0], 3);
+        AttributeFormat::U8.write(300.0, &mut data[..1]);
+        assert_eq!(data[0], 255);
+    }
+}
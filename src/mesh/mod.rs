@@ -1,292 +1,298 @@
-use gl;
-use std::os::raw::c_void;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
 
-// Note: This should probably be a trait.
+use crate::shaders::reflection::ActiveAttribute;
+use crate::shaders::Program;
 
-#[derive(Debug)]
-pub struct Mesh {
-    pub vertices: Vec<f32>,
-    pub indices: Option<Vec<u32>>,
-    pub normals: Option<Vec<f32>>,
-    pub uv: Option<Vec<f32>>,
+use self::bounds::{Aabb, BoundingSphere};
+use self::data::MeshData;
+use self::gpu::GpuMesh;
+use self::obj::{ObjError, ObjModel};
 
-    pub vbo_vertices: Option<u32>,
-    pub vbo_indices: Option<u32>,
-    pub vbo_normals: Option<u32>,
-    pub vbo_uv: Option<u32>,
-    pub vao: Option<u32>,
+pub mod bounds;
+pub mod builder;
+pub mod data;
+pub mod gpu;
+pub mod layout;
+pub mod normals;
+pub mod obj;
+pub mod primitives;
+pub mod tangents;
 
-    pub v_components: i32,
-    pub n_components: i32,
-    pub uv_components: i32,
-    pub draw_type: u32,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub location: u32,
+    pub components: i32,
+    pub integer: bool,
 }
 
-fn gen_vbo() -> Option<u32> {
-    let mut vbo_addr: u32 = 0;
-    unsafe {
-        gl::GenBuffers(1, &mut vbo_addr);
-    }
-    Some(vbo_addr)
+#[derive(Debug)]
+pub enum AttributeError {
+    Missing {
+        name: String,
+        location: i32,
+    },
+    Components {
+        name: String,
+        location: i32,
+        expected: i32,
+        found: i32,
+    },
+    NotFloat {
+        name: String,
+        location: i32,
+    },
+    NotInteger {
+        name: String,
+        location: i32,
+    },
 }
 
-impl Drop for Mesh {
-    fn drop(&mut self) {
-        if self.vbo_vertices.is_some() {
-            unsafe {
-                gl::DeleteBuffers(1, &self.vbo_vertices.unwrap());
-            }
+impl fmt::Display for AttributeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AttributeError::Missing { name, location } => write!(
+                f,
+                "attribute {} (location {}) isn't provided by the mesh",
+                name, location
+            ),
+            AttributeError::Components {
+                name,
+                location,
+                expected,
+                found,
+            } => write!(
+                f,
+                "attribute {} (location {}) expects {} components, the mesh provides {}",
+                name, location, expected, found
+            ),
+            AttributeError::NotFloat { name, location } => write!(
+                f,
+                "attribute {} (location {}) is an integer input, the mesh provides floats",
+                name, location
+            ),
+            AttributeError::NotInteger { name, location } => write!(
+                f,
+                "attribute {} (location {}) is a float input, the mesh provides integers",
+                name, location
+            ),
         }
+    }
+}
 
-        if self.vbo_indices.is_some() {
-            unsafe {
-                gl::DeleteBuffers(1, &self.vbo_indices.unwrap());
-            }
-        }
+impl Error for AttributeError {}
 
-        if self.vbo_normals.is_some() {
-            unsafe {
-                gl::DeleteBuffers(1, &self.vbo_normals.unwrap());
-            }
-        }
+// Matches the attributes a mesh provides with the vertex inputs of a program,
+// its reflected `attributes`.
+fn validate_attributes(
+    provided: &[VertexAttribute],
+    inputs: &HashMap<String, ActiveAttribute>,
+) -> Result<(), Vec<AttributeError>> {
+    let mut errors = Vec::new();
 
-        if self.vbo_uv.is_some() {
-            unsafe {
-                gl::DeleteBuffers(1, &self.vbo_uv.unwrap());
-            }
-        }
+    let mut inputs: Vec<_> = inputs.values().collect();
+    inputs.sort_by_key(|input| input.location);
+    for input in inputs {
+        let name = input.name.clone();
+        let location = input.location;
+        let attribute = provided
+            .iter()
+            .find(|attribute| attribute.location as i32 == location);
 
-        if self.vao.is_some() {
-            unsafe {
-                gl::DeleteVertexArrays(1, &self.vao.unwrap());
+        match attribute {
+            None => errors.push(AttributeError::Missing { name, location }),
+            Some(attribute) if attribute.integer && input.attribute_type.is_float() => {
+                errors.push(AttributeError::NotInteger { name, location })
             }
+            Some(attribute) if !attribute.integer && !input.attribute_type.is_float() => {
+                errors.push(AttributeError::NotFloat { name, location })
+            }
+            Some(attribute) if attribute.components < input.attribute_type.components() => errors
+                .push(AttributeError::Components {
+                    name,
+                    location,
+                    expected: input.attribute_type.components(),
+                    found: attribute.components,
+                }),
+            Some(_) => {}
         }
     }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
 }
 
-impl Mesh {
-    fn bind_vao(&mut self) {
-        if self.vao.is_none() {
-            let mut vao_addr: u32 = 0;
-            unsafe {
-                gl::GenVertexArrays(1, &mut vao_addr);
-            }
-            self.vao = Some(vao_addr);
-        }
-        unsafe {
-            gl::BindVertexArray(self.vao.unwrap());
-        }
+// Mesh data with its GPU resources once `ready_up` uploaded it. Meshes that
+// never get drawn, or are drawn by several owners, are better kept as
+// `MeshData` and `GpuMesh`.
+#[derive(Debug)]
+pub struct Mesh {
+    pub data: MeshData,
+    pub gpu: Option<GpuMesh>,
+    // Computed with the mesh, `update_bounds` after editing `data`.
+    pub aabb: Option<Aabb>,
+    pub bounding_sphere: Option<BoundingSphere>,
+}
+
+impl From<MeshData> for Mesh {
+    fn from(data: MeshData) -> Mesh {
+        Mesh::new(data)
     }
+}
 
-    fn free_vao(&self) {
-        unsafe {
-            gl::BindVertexArray(0);
+impl Mesh {
+    pub fn new(data: MeshData) -> Mesh {
+        let aabb = data.aabb();
+        let bounding_sphere = data.bounding_sphere();
+        Mesh {
+            data,
+            gpu: None,
+            aabb,
+            bounding_sphere,
         }
     }
 
-    fn enable_attrib(&mut self) {
-        self.bind_vao();
+    pub fn cube() -> Mesh {
+        Mesh::new(MeshData::cube())
+    }
 
-        if self.vbo_indices.is_some() {
-            unsafe {
-                gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo_vertices.unwrap());
-                gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.vbo_indices.unwrap());
-            }
-        } else {
-            unsafe {
-                gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo_vertices.unwrap());
-            }
-        }
+    pub fn fs_quad() -> Mesh {
+        Mesh::new(MeshData::fs_quad())
+    }
 
-        unsafe {
-            gl::EnableVertexAttribArray(0);
-            gl::VertexAttribPointer(
-                0,
-                self.v_components,
-                gl::FLOAT,
-                gl::FALSE,
-                0,
-                std::ptr::null_mut(),
-            );
-        }
+    // Loads the whole file as one mesh, see `ObjModel` to keep the groups.
+    pub fn from_obj(path: &Path) -> Result<Mesh, ObjError> {
+        ObjModel::load(path).map(|model| Mesh::new(model.mesh))
+    }
 
-        if self.vbo_normals.is_some() {
-            unsafe {
-                gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo_normals.unwrap());
-                gl::EnableVertexAttribArray(1);
-                gl::VertexAttribPointer(
-                    1,
-                    self.n_components,
-                    gl::FLOAT,
-                    gl::FALSE,
-                    0,
-                    std::ptr::null_mut(),
-                );
-            }
+    // Uploads the data unless it already was.
+    pub fn ready_up(&mut self) {
+        if self.gpu.is_none() {
+            self.gpu = Some(self.data.upload());
         }
+    }
 
-        if self.vbo_uv.is_some() {
-            unsafe {
-                gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo_uv.unwrap());
-                gl::EnableVertexAttribArray(2);
-                gl::VertexAttribPointer(
-                    2,
-                    self.uv_components,
-                    gl::FLOAT,
-                    gl::FALSE,
-                    0,
-                    std::ptr::null_mut(),
-                );
-            }
-        }
+    pub fn update_bounds(&mut self) {
+        self.aabb = self.data.aabb();
+        self.bounding_sphere = self.data.bounding_sphere();
+    }
 
-        self.free_vao();
+    pub fn vertex_attributes(&self) -> Vec<VertexAttribute> {
+        self.data.vertex_attributes()
+    }
 
-        unsafe {
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
-        }
+    pub fn validate(&self, program: &Program) -> Result<(), Vec<AttributeError>> {
+        self.data.validate(program)
     }
 
-    fn upload(&mut self) {
-        if self.vbo_vertices.is_none() {
-            self.vbo_vertices = gen_vbo();
-            unsafe {
-                gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo_vertices.unwrap());
-                gl::BufferData(
-                    gl::ARRAY_BUFFER,
-                    (std::mem::size_of::<f32>() * self.vertices.len()) as isize,
-                    self.vertices.as_mut_ptr() as *const c_void,
-                    gl::STATIC_DRAW,
-                );
-            }
+    pub fn set_patches(&mut self, vertices: i32) {
+        self.data.set_patches(vertices);
+        if let Some(gpu) = &mut self.gpu {
+            gpu.draw_type = self.data.draw_type;
+            gpu.patch_vertices = vertices;
         }
+    }
 
-        if self.vbo_indices.is_none() {
-            if let Some(ind) = &mut self.indices {
-                self.vbo_indices = gen_vbo();
-                unsafe {
-                    gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.vbo_indices.unwrap());
-                    gl::BufferData(
-                        gl::ELEMENT_ARRAY_BUFFER,
-                        (std::mem::size_of::<u32>() * ind.len()) as isize,
-                        ind.as_mut_ptr() as *const c_void,
-                        gl::STATIC_DRAW,
-                    );
-                }
-            }
+    // Drawing does nothing until `ready_up` was called.
+    pub fn draw(&self) {
+        if let Some(gpu) = &self.gpu {
+            gpu.draw();
         }
+    }
 
-        if self.vbo_normals.is_none() {
-            if let Some(norms) = &mut self.normals {
-                self.vbo_normals = gen_vbo();
-                unsafe {
-                    gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo_normals.unwrap());
-                    gl::BufferData(
-                        gl::ARRAY_BUFFER,
-                        (std::mem::size_of::<f32>() * norms.len()) as isize,
-                        norms.as_mut_ptr() as *const c_void,
-                        gl::STATIC_DRAW,
-                    );
-                }
-            }
-        }
+    pub fn draw_with(&self, program: &Program) -> Result<(), Vec<AttributeError>> {
+        self.validate(program)?;
+        self.draw();
+        Ok(())
+    }
 
-        if self.vbo_uv.is_none() {
-            if let Some(uv) = &mut self.uv {
-                self.vbo_uv = gen_vbo();
-                unsafe {
-                    gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo_uv.unwrap());
-                    gl::BufferData(
-                        gl::ARRAY_BUFFER,
-                        (std::mem::size_of::<f32>() * uv.len()) as isize,
-                        uv.as_mut_ptr() as *const c_void,
-                        gl::STATIC_DRAW,
-                    );
-                }
-            }
-        }
-        unsafe {
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+    pub fn draw_range(&self, range: Range<usize>) {
+        if let Some(gpu) = &self.gpu {
+            gpu.draw_range(range);
         }
     }
+}
 
-    pub fn ready_up(&mut self) {
-        self.upload();
-        self.enable_attrib();
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shaders::reflection::UniformType;
 
-    pub fn draw(&mut self) {
-        self.bind_vao();
+    fn inputs(attributes: &[(&str, UniformType, i32)]) -> HashMap<String, ActiveAttribute> {
+        attributes
+            .iter()
+            .map(|(name, attribute_type, location)| {
+                let attribute = ActiveAttribute {
+                    name: name.to_string(),
+                    attribute_type: *attribute_type,
+                    size: 1,
+                    location: *location,
+                };
+                (name.to_string(), attribute)
+            })
+            .collect()
+    }
 
-        if self.vbo_indices.is_some() {
-            let fnb = self.indices.as_mut().map_or(0, |ind| ind.len() as i32);
-            unsafe {
-                gl::DrawElements(self.draw_type, fnb, gl::UNSIGNED_INT, std::ptr::null_mut());
-            }
-        } else {
-            unsafe { gl::DrawArrays(self.draw_type, 0, (self.vertices.len() / 3) as i32) }
+    fn provided(location: u32, components: i32, integer: bool) -> VertexAttribute {
+        VertexAttribute {
+            location,
+            components,
+            integer,
         }
-
-        self.free_vao();
     }
 
-    pub fn cube() -> Mesh {
-        Mesh {
-            v_components: 3,
-            vertices: vec![
-                -1.000000, -1.000000, 1.000000, -1.000000, 1.000000, 1.000000, -1.000000,
-                -1.000000, -1.000000, -1.000000, 1.000000, -1.000000, 1.000000, -1.000000,
-                1.000000, 1.000000, 1.000000, 1.000000, 1.000000, -1.000000, -1.000000, 1.000000,
-                1.000000, -1.000000,
-            ],
-            indices: Some(vec![
-                1, 2, 0, 3, 6, 2, 7, 4, 6, 5, 0, 4, 6, 0, 2, 3, 5, 7, 1, 3, 2, 3, 7, 6, 7, 5, 4, 5,
-                1, 0, 6, 4, 0, 3, 1, 5,
-            ]),
-            n_components: 3,
-            normals: Some(vec![
-                -1.0000, 0.0000, 0.0000, 0.0000, 0.0000, -1.0000, 1.0000, 0.0000, 0.0000, 0.0000,
-                0.0000, 1.0000, 0.0000, -1.0000, 0.0000, 0.0000, 1.0000, 0.0000,
-            ]),
-            uv_components: 2,
-            uv: Some(vec![
-                0.000200, 0.666866, 0.333134, 0.999800, 0.000200, 0.999800, 0.666866, 0.000200,
-                0.999800, 0.333134, 0.666866, 0.333134, 0.333134, 0.666467, 0.000200, 0.333533,
-                0.333134, 0.333533, 0.666467, 0.666467, 0.333533, 0.333533, 0.666467, 0.333533,
-                0.333533, 0.333134, 0.666467, 0.000200, 0.666467, 0.333134, 0.000200, 0.333134,
-                0.333134, 0.000200, 0.333134, 0.333134, 0.333134, 0.666866, 0.999800, 0.000200,
-                0.000200, 0.666467, 0.333533, 0.666467, 0.333533, 0.000200, 0.000200, 0.000200,
-            ]),
-            vbo_vertices: None,
-            vbo_indices: None,
-            vbo_normals: None,
-            vbo_uv: None,
-            vao: None,
-            draw_type: gl::TRIANGLES,
-        }
+    #[test]
+    fn matching_attributes_validate() {
+        let inputs = inputs(&[("pos", UniformType::VEC3, 0), ("uv", UniformType::VEC2, 2)]);
+        // More components than used are fine.
+        let provided = [provided(0, 4, false), provided(2, 2, false)];
+        assert!(validate_attributes(&provided, &inputs).is_ok());
     }
 
-    pub fn fs_quad() -> Mesh {
-        Mesh {
-            v_components: 2,
-            vertices: vec![
-                -1.0, 1.0, -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, 1.0,
-            ],
-            uv_components: 2,
-            uv: Some(vec![
-                0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 1.0,
-            ]),
-            n_components: 0,
-            normals: None,
-            indices: None,
-            vbo_vertices: None,
-            vbo_indices: None,
-            vbo_normals: None,
-            vbo_uv: None,
-            vao: None,
-            draw_type: gl::TRIANGLES,
-        }
+    #[test]
+    fn each_mismatch_is_reported_by_location() {
+        let inputs = inputs(&[
+            ("pos", UniformType::VEC3, 0),
+            ("normal", UniformType::VEC3, 1),
+            ("uv", UniformType::VEC2, 2),
+            ("joints", UniformType::IVEC4, 3),
+            ("weights", UniformType::VEC4, 4),
+        ]);
+        let provided = [
+            provided(0, 2, false),
+            provided(2, 2, true),
+            provided(3, 4, false),
+            provided(4, 4, false),
+        ];
+
+        let errors = validate_attributes(&provided, &inputs).unwrap_err();
+        assert_eq!(errors.len(), 4);
+        assert!(matches!(
+            &errors[0],
+            AttributeError::Components {
+                location: 0,
+                expected: 3,
+                found: 2,
+                ..
+            }
+        ));
+        assert!(matches!(
+            &errors[1],
+            AttributeError::Missing { name, location: 1 } if name == "normal"
+        ));
+        assert!(matches!(
+            &errors[2],
+            AttributeError::NotInteger { location: 2, .. }
+        ));
+        assert!(matches!(
+            &errors[3],
+            AttributeError::NotFloat { location: 3, .. }
+        ));
     }
 }
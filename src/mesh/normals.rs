@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Rad, Vector3};
+
+use super::data::{MeshData, MeshError};
+
+// How the faces around a vertex contribute to its normal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalWeighting {
+    // By face area, so small faces barely bend the normal.
+    Area,
+    // By the angle of the face at the vertex, independent of how faces are
+    // split into triangles.
+    Angle,
+}
+
+fn normalize_or_up(normal: Vector3<f32>) -> Vector3<f32> {
+    if normal.magnitude2() > 0.0 {
+        normal.normalize()
+    } else {
+        Vector3::new(0.0, 1.0, 0.0)
+    }
+}
+
+fn position_key(position: &Vector3<f32>) -> [u32; 3] {
+    [
+        position.x.to_bits(),
+        position.y.to_bits(),
+        position.z.to_bits(),
+    ]
+}
+
+// Faces of a triangle mesh with their adjacency. Vertices at the same
+// position share their faces, so seams of texture coordinates stay smooth.
+struct Faces {
+    triangles: Vec<[u32; 3]>,
+    positions: Vec<Vector3<f32>>,
+    // Cross product of two edges, twice the area long.
+    normals: Vec<Vector3<f32>>,
+    // Group of vertices at the same position of each vertex.
+    groups: Vec<usize>,
+    // Faces touching each group, with the corner at the group.
+    group_faces: Vec<Vec<(usize, usize)>>,
+}
+
+impl Faces {
+    fn new(mesh: &MeshData) -> Result<Faces, MeshError> {
+        if mesh.interleaved.is_some() {
+            return Err(MeshError::Interleaved);
+        }
+        mesh.check()?;
+
+        let positions = mesh.positions();
+        let triangles = mesh.triangles()?;
+
+        let normals = triangles
+            .iter()
+            .map(|t| {
+                let [a, b, c] = t.map(|index| positions[index as usize]);
+                (b - a).cross(c - a)
+            })
+            .collect();
+
+        let mut keys: HashMap<[u32; 3], usize> = HashMap::new();
+        let groups: Vec<usize> = positions
+            .iter()
+            .map(|position| {
+                let count = keys.len();
+                *keys.entry(position_key(position)).or_insert(count)
+            })
+            .collect();
+
+        let mut group_faces = vec![Vec::new(); keys.len()];
+        for (face, triangle) in triangles.iter().enumerate() {
+            for (corner, index) in triangle.iter().enumerate() {
+                group_faces[groups[*index as usize]].push((face, corner));
+            }
+        }
+
+        Ok(Faces {
+            triangles,
+            positions,
+            normals,
+            groups,
+            group_faces,
+        })
+    }
+
+    fn weighted_normal(
+        &self,
+        face: usize,
+        corner: usize,
+        weighting: NormalWeighting,
+    ) -> Vector3<f32> {
+        match weighting {
+            NormalWeighting::Area => self.normals[face],
+            NormalWeighting::Angle => {
+                let triangle = self.triangles[face];
+                let position = |k: usize| self.positions[triangle[k % 3] as usize];
+                let ab = position(corner + 1) - position(corner);
+                let ac = position(corner + 2) - position(corner);
+                if ab.magnitude2() == 0.0 || ac.magnitude2() == 0.0 {
+                    return Vector3::new(0.0, 0.0, 0.0);
+                }
+                let cos = ab.normalize().dot(ac.normalize()).clamp(-1.0, 1.0);
+                normalize_or_up(self.normals[face]) * cos.acos()
+            }
+        }
+    }
+
+    // Normal of `corner` of `face`, from the faces around it whose normal is
+    // at most the angle of cosine `min_cos` away from the face's.
+    fn corner_normal(
+        &self,
+        face: usize,
+        corner: usize,
+        min_cos: f32,
+        weighting: NormalWeighting,
+    ) -> Vector3<f32> {
+        let face_normal = normalize_or_up(self.normals[face]);
+        let group = self.groups[self.triangles[face][corner] as usize];
+        let sum = self.group_faces[group]
+            .iter()
+            .filter(|(other, _)| {
+                *other == face || normalize_or_up(self.normals[*other]).dot(face_normal) >= min_cos
+            })
+            .fold(Vector3::new(0.0, 0.0, 0.0), |sum, (other, other_corner)| {
+                sum + self.weighted_normal(*other, *other_corner, weighting)
+            });
+
+        if sum.magnitude2() > 0.0 {
+            sum.normalize()
+        } else {
+            face_normal
+        }
+    }
+}
+
+impl MeshData {
+    // One normal per vertex, averaging the faces around its position. The
+    // vertices are kept as they are, tangents are dropped.
+    pub fn smooth_normals(&mut self, weighting: NormalWeighting) -> Result<(), MeshError> {
+        let faces = Faces::new(self)?;
+
+        let mut normals = vec![0.0; faces.positions.len() * 3];
+        for (vertex, group) in faces.groups.iter().enumerate() {
+            let sum = faces.group_faces[*group]
+                .iter()
+                .fold(Vector3::new(0.0, 0.0, 0.0), |sum, (face, corner)| {
+                    sum + faces.weighted_normal(*face, *corner, weighting)
+                });
+            let normal: [f32; 3] = normalize_or_up(sum).into();
+            normals[vertex * 3..vertex * 3 + 3].copy_from_slice(&normal);
+        }
+
+        self.normals = Some(normals);
+        self.n_components = 3;
+        self.tangents = None;
+        Ok(())
+    }
+
+    // The normal of each face on all its corners, vertices shared by faces
+    // not facing the same way are split.
+    pub fn flat_normals(&mut self) -> Result<(), MeshError> {
+        let faces = Faces::new(self)?;
+        let normals = faces
+            .triangles
+            .iter()
+            .enumerate()
+            .map(|(face, _)| [normalize_or_up(faces.normals[face]); 3])
+            .collect();
+        self.set_corner_normals(&faces.triangles, normals);
+        Ok(())
+    }
+
+    // Smooth normals across edges whose faces are at most `angle` apart,
+    // split beyond it: 0 gives flat normals, 180 degrees smooth ones.
+    pub fn crease_normals<A: Into<Rad<f32>>>(
+        &mut self,
+        angle: A,
+        weighting: NormalWeighting,
+    ) -> Result<(), MeshError> {
+        let faces = Faces::new(self)?;
+        // Slightly lenient so that coplanar faces always match.
+        let min_cos = angle.into().0.cos() - 1e-5;
+        let normals = (0..faces.triangles.len())
+            .map(|face| {
+                [0, 1, 2].map(|corner| faces.corner_normal(face, corner, min_cos, weighting))
+            })
+            .collect();
+        self.set_corner_normals(&faces.triangles, normals);
+        Ok(())
+    }
+
+    // Sets the normal of every corner, one vertex being created for each
+    // distinct normal of an original vertex. Tangents, which depend on the
+    // normals, are dropped.
+    fn set_corner_normals(&mut self, triangles: &[[u32; 3]], normals: Vec<[Vector3<f32>; 3]>) {
+        let mut split: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+        let mut sources: Vec<u32> = Vec::new();
+        let mut new_normals: Vec<f32> = Vec::new();
+        let mut indices = Vec::with_capacity(triangles.len() * 3);
+
+        for (triangle, normals) in triangles.iter().zip(normals) {
+            for (index, normal) in triangle.iter().zip(normals.iter()) {
+                // Adding 0 turns -0 into 0, which would split vertices.
+                let key = (
+                    *index,
+                    position_key(&(normal + Vector3::new(0.0, 0.0, 0.0))),
+                );
+                let new_index = *split.entry(key).or_insert_with(|| {
+                    sources.push(*index);
+                    new_normals.extend_from_slice(&[normal.x, normal.y, normal.z]);
+                    (sources.len() - 1) as u32
+                });
+                indices.push(new_index);
+            }
+        }
+
+        self.remap_vertices(&sources, indices);
+        self.normals = Some(new_normals);
+        self.n_components = 3;
+        self.tangents = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Deg;
+
+    fn normal(mesh: &MeshData, vertex: usize) -> Vector3<f32> {
+        let normals = mesh.normals.as_ref().unwrap();
+        Vector3::new(
+            normals[vertex * 3],
+            normals[vertex * 3 + 1],
+            normals[vertex * 3 + 2],
+        )
+    }
+
+    // Position and normal of every corner of every triangle, which don't
+    // depend on how the vertices are ordered.
+    fn corners(mesh: &MeshData) -> Vec<(Vector3<f32>, Vector3<f32>)> {
+        let positions = mesh.positions();
+        let triangles = mesh.triangles().unwrap();
+        triangles
+            .iter()
+            .flatten()
+            .map(|vertex| (positions[*vertex as usize], normal(mesh, *vertex as usize)))
+            .collect()
+    }
+
+    fn assert_same_corners(a: &MeshData, b: &MeshData) {
+        for ((pa, na), (pb, nb)) in corners(a).into_iter().zip(corners(b)) {
+            assert_eq!(pa, pb);
+            assert!((na - nb).magnitude() < 1e-5, "{:?} != {:?}", na, nb);
+        }
+    }
+
+    // The cube with its 8 corners shared by the 3 faces around them.
+    fn welded_cube() -> MeshData {
+        let cube = MeshData::cube();
+        let mut vertices: Vec<Vector3<f32>> = Vec::new();
+        let mut indices = Vec::new();
+        let positions = cube.positions();
+        for vertex in cube.triangles().unwrap().iter().flatten() {
+            let position = positions[*vertex as usize];
+            let index = match vertices.iter().position(|other| *other == position) {
+                Some(index) => index,
+                None => {
+                    vertices.push(position);
+                    vertices.len() - 1
+                }
+            };
+            indices.push(index as u32);
+        }
+        MeshData {
+            vertices: vertices.iter().flat_map(|v| [v.x, v.y, v.z]).collect(),
+            indices: Some(indices),
+            ..MeshData::default()
+        }
+    }
+
+    #[test]
+    fn smooth_cube_normals_are_the_diagonals() {
+        let mut cube = MeshData::cube();
+        let vertices = cube.vertex_count();
+        cube.smooth_normals(NormalWeighting::Angle).unwrap();
+        assert_eq!(cube.vertex_count(), vertices);
+        for (vertex, position) in cube.positions().iter().enumerate() {
+            let diagonal = position.normalize();
+            assert!((normal(&cube, vertex) - diagonal).magnitude() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn flat_normals_split_shared_corners() {
+        let mut cube = welded_cube();
+        assert_eq!(cube.vertex_count(), 8);
+        cube.flat_normals().unwrap();
+        assert_eq!(cube.vertex_count(), 24);
+        assert_eq!(cube.element_count(), 36);
+        for (position, normal) in corners(&cube) {
+            // Each face normal points along the axis the face is flat on.
+            assert_eq!(normal.magnitude(), 1.0);
+            assert_eq!(position.dot(normal), 1.0);
+        }
+    }
+
+    #[test]
+    fn crease_angle_picks_flat_or_smooth() {
+        let mut flat = welded_cube();
+        flat.flat_normals().unwrap();
+        let mut creased = welded_cube();
+        creased
+            .crease_normals(Deg(30.0), NormalWeighting::Angle)
+            .unwrap();
+        assert_eq!(creased.vertex_count(), 24);
+        assert_same_corners(&creased, &flat);
+
+        let mut smooth = welded_cube();
+        smooth.smooth_normals(NormalWeighting::Angle).unwrap();
+        let mut creased = welded_cube();
+        creased
+            .crease_normals(Deg(180.0), NormalWeighting::Angle)
+            .unwrap();
+        assert_eq!(creased.vertex_count(), 8);
+        assert_same_corners(&creased, &smooth);
+    }
+
+    // Two triangles around the origin: a small one with a wide angle facing
+    // +Z and a large one with a narrow angle facing +X.
+    #[test]
+    fn area_and_angle_weighting_differ() {
+        let fan = MeshData {
+            vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.2, 3.0],
+            indices: Some(vec![0, 1, 2, 0, 2, 3]),
+            ..MeshData::default()
+        };
+
+        let mut area = fan.clone();
+        area.smooth_normals(NormalWeighting::Area).unwrap();
+        let mut angle = fan;
+        angle.smooth_normals(NormalWeighting::Angle).unwrap();
+
+        let expected = Vector3::new(3.0, 0.0, 1.0).normalize();
+        assert!((normal(&area, 0) - expected).magnitude() < 1e-5);
+        assert!((normal(&angle, 0) - normal(&area, 0)).magnitude() > 0.1);
+    }
+}
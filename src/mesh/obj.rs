@@ -0,0 +1,316 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::utils;
+
+use super::builder::{Corner, MeshBuilder};
+use super::data::MeshData;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io { path, error } => {
+                write!(f, "couldn't read {} : {}", path.display(), error)
+            }
+            ObjError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ObjError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+// Faces of the same object, group and material, as a range of the index
+// buffer which can be drawn with `GpuMesh::draw_range`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjGroup {
+    pub object: String,
+    pub group: String,
+    pub material: Option<String>,
+    pub indices: Range<usize>,
+}
+
+#[derive(Debug)]
+pub struct ObjModel {
+    pub mesh: MeshData,
+    pub groups: Vec<ObjGroup>,
+}
+
+// Indices of the position, texture coordinate and normal of a face corner.
+type CornerIndices = (usize, Option<usize>, Option<usize>);
+
+fn parse_floats(words: &[&str], min: usize, max: usize) -> Option<Vec<f32>> {
+    if words.len() < min || words.len() > max {
+        return None;
+    }
+    words.iter().map(|word| word.parse().ok()).collect()
+}
+
+// OBJ indices start at 1, negative ones count back from the last element
+// declared so far.
+fn resolve_index(word: &str, count: usize) -> Result<usize, String> {
+    let index: i64 = word
+        .parse()
+        .map_err(|_| format!("invalid index {}", word))?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("index {} out of range, {} declared", index, count));
+    }
+    Ok(resolved as usize)
+}
+
+fn parse_corner(word: &str, counts: (usize, usize, usize)) -> Result<CornerIndices, String> {
+    let mut parts = word.split('/');
+    let position = resolve_index(parts.next().unwrap_or(""), counts.0)?;
+    let uv = match parts.next() {
+        None | Some("") => None,
+        Some(index) => Some(resolve_index(index, counts.1)?),
+    };
+    let normal = match parts.next() {
+        None | Some("") => None,
+        Some(index) => Some(resolve_index(index, counts.2)?),
+    };
+    if parts.next().is_some() {
+        return Err(format!("invalid face vertex {}", word));
+    }
+    Ok((position, uv, normal))
+}
+
+impl ObjModel {
+    // Parses the subset of Wavefront OBJ used for meshes: `v`, `vt`, `vn`,
+    // `f` with `v`, `v/vt`, `v//vn` or `v/vt/vn` corners, `o`, `g` and
+    // `usemtl`. Faces are added to a `MeshBuilder`, so polygons must be
+    // convex and identical corners are welded. Other statements
+    // (`mtllib`, `s`, lines and points) are ignored.
+    pub fn parse(src: &str, path: &Path) -> Result<ObjModel, ObjError> {
+        let mut positions: Vec<[f32; 3]> = Vec::new();
+        let mut uvs: Vec<[f32; 2]> = Vec::new();
+        let mut normals: Vec<[f32; 3]> = Vec::new();
+
+        let mut builder = MeshBuilder::new();
+
+        let mut groups: Vec<ObjGroup> = Vec::new();
+        let mut current = ObjGroup {
+            object: String::new(),
+            group: String::new(),
+            material: None,
+            indices: 0..0,
+        };
+
+        for (number, line) in src.lines().enumerate() {
+            let error = |message: &str| ObjError::Parse {
+                path: path.to_path_buf(),
+                line: number + 1,
+                message: message.to_string(),
+            };
+
+            let line = line.split('#').next().unwrap_or("").trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+
+            match words[0] {
+                // Optional w or vertex colors after x, y and z are dropped.
+                "v" => {
+                    let v = parse_floats(&words[1..], 3, 7)
+                        .ok_or_else(|| error("expected 3 to 7 position values"))?;
+                    positions.push([v[0], v[1], v[2]]);
+                }
+                "vt" => {
+                    let v = parse_floats(&words[1..], 1, 3)
+                        .ok_or_else(|| error("expected 1 to 3 texture coordinates"))?;
+                    uvs.push([v[0], v.get(1).cloned().unwrap_or(0.0)]);
+                }
+                "vn" => {
+                    let v = parse_floats(&words[1..], 3, 3)
+                        .ok_or_else(|| error("expected 3 normal values"))?;
+                    normals.push([v[0], v[1], v[2]]);
+                }
+                "f" => {
+                    if words.len() < 4 {
+                        return Err(error("a face needs at least 3 vertices"));
+                    }
+                    let counts = (positions.len(), uvs.len(), normals.len());
+                    let mut face = Vec::with_capacity(words.len() - 1);
+                    for word in &words[1..] {
+                        let (position, uv, normal) =
+                            parse_corner(word, counts).map_err(|message| error(&message))?;
+                        face.push(Corner {
+                            position: positions[position],
+                            normal: normal.map(|normal| normals[normal]),
+                            uv: uv.map(|uv| uvs[uv]),
+                        });
+                    }
+                    builder.polygon(&face);
+                }
+                "o" | "g" | "usemtl" => {
+                    let name = words[1..].join(" ");
+                    let end = builder.index_count();
+                    current.indices.end = end;
+                    let mut next = ObjGroup {
+                        indices: end..end,
+                        ..current.clone()
+                    };
+                    match words[0] {
+                        "o" => {
+                            next.object = name;
+                            next.group = String::new();
+                        }
+                        "g" => next.group = name,
+                        _ => next.material = Some(name),
+                    }
+                    if !current.indices.is_empty() {
+                        groups.push(current);
+                    }
+                    current = next;
+                }
+                _ => {}
+            }
+        }
+
+        current.indices.end = builder.index_count();
+        if !current.indices.is_empty() {
+            groups.push(current);
+        }
+
+        if builder.index_count() == 0 {
+            return Err(ObjError::Parse {
+                path: path.to_path_buf(),
+                line: 0,
+                message: "no face".to_string(),
+            });
+        }
+
+        Ok(ObjModel {
+            mesh: builder.build(),
+            groups,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<ObjModel, ObjError> {
+        #[cfg(feature = "debug")]
+        println!("[NFO] Loading mesh {}", path.display());
+
+        let src = utils::load_file(path).map_err(|error| ObjError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        ObjModel::parse(&src, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(src: &str) -> Result<ObjModel, ObjError> {
+        ObjModel::parse(src, Path::new("test.obj"))
+    }
+
+    const QUAD: &str = "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+";
+
+    #[test]
+    fn quad_is_triangulated() {
+        let model = parse(&format!("{}f 1 2 3 4\n", QUAD)).unwrap();
+        assert_eq!(model.mesh.vertex_count(), 4);
+        assert_eq!(model.mesh.indices, Some(vec![0, 1, 2, 0, 2, 3]));
+        assert_eq!(model.mesh.normals, None);
+        assert_eq!(model.mesh.uv, None);
+    }
+
+    #[test]
+    fn corners_are_de_indexed() {
+        // The same position with two texture coordinates makes two vertices.
+        let model = parse(&format!(
+            "{}f 1/1/1 2/2/1 3/3/1\nf 1/4/1 3/3/1 4/4/1\n",
+            QUAD
+        ))
+        .unwrap();
+        let mesh = &model.mesh;
+        mesh.check().unwrap();
+        assert_eq!(mesh.vertex_count(), 5);
+        assert_eq!(mesh.indices, Some(vec![0, 1, 2, 3, 2, 4]));
+        assert_eq!(&mesh.vertices[9..12], &[0.0, 0.0, 0.0]);
+        assert_eq!(&mesh.uv.as_ref().unwrap()[6..8], &[0.0, 1.0]);
+        assert_eq!(&mesh.normals.as_ref().unwrap()[12..15], &[0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn materials_split_groups() {
+        let model = parse(&format!(
+            "{}o quad\nusemtl red\nf 1 2 3\nusemtl blue\nf 1 3 4\nf 1 2 4\n",
+            QUAD
+        ))
+        .unwrap();
+        let ranges: Vec<_> = model
+            .groups
+            .iter()
+            .map(|group| {
+                (
+                    group.object.as_str(),
+                    group.material.as_deref(),
+                    group.indices.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![("quad", Some("red"), 0..3), ("quad", Some("blue"), 3..9)]
+        );
+    }
+
+    #[test]
+    fn bad_index_reports_its_line() {
+        match parse(&format!("{}f 1 2 3\n\nf 1 2 9\n", QUAD)) {
+            Err(ObjError::Parse { line, message, .. }) => {
+                assert_eq!(line, 12);
+                assert!(message.contains("out of range"), "{}", message);
+            }
+            other => panic!(
+                "expected a parse error, got {:?}",
+                other.map(|model| model.groups)
+            ),
+        }
+    }
+}
@@ -0,0 +1,454 @@
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use cgmath::{InnerSpace, Vector3};
+
+use super::builder::{Corner, MeshBuilder};
+use super::data::MeshData;
+
+// Procedural meshes, centered on the origin with Y up. Triangles are counter
+// clockwise seen from outside and normals are unit length. Zero segments or
+// rings give an empty mesh. Only CPU side data is built, `MeshData::upload`
+// creates the GPU side.
+
+fn corner(position: Vector3<f32>, normal: Vector3<f32>, uv: [f32; 2]) -> Corner {
+    Corner::new(position.into())
+        .with_normal(normal.normalize().into())
+        .with_uv(uv)
+}
+
+// Skips the triangles collapsed at poles and apexes, where an edge is
+// shorter than rounding errors.
+fn triangle(builder: &mut MeshBuilder, a: Corner, b: Corner, c: Corner) {
+    let position = |corner: Corner| Vector3::from(corner.position);
+    let edges = [
+        position(b) - position(a),
+        position(c) - position(b),
+        position(a) - position(c),
+    ]
+    .map(|edge| edge.magnitude());
+    let longest = edges.iter().cloned().fold(0.0, f32::max);
+    if edges.iter().all(|edge| *edge > longest * 1e-5) {
+        builder.triangle(a, b, c);
+    }
+}
+
+// Surface of `columns` by `rows` quads whose corner (i, j) is given by
+// `corner_at`. The outward side is the one the cross product of the i and j
+// directions points to.
+fn grid<F: Fn(usize, usize) -> Corner>(
+    builder: &mut MeshBuilder,
+    columns: usize,
+    rows: usize,
+    corner_at: F,
+) {
+    for j in 0..rows {
+        for i in 0..columns {
+            let a = corner_at(i, j);
+            let b = corner_at(i + 1, j);
+            let c = corner_at(i + 1, j + 1);
+            let d = corner_at(i, j + 1);
+            triangle(builder, a, b, c);
+            triangle(builder, a, c, d);
+        }
+    }
+}
+
+// Disk of the caps of cylinders and cones, facing +Y or -Y.
+fn disk(builder: &mut MeshBuilder, radius: f32, y: f32, segments: usize, up: bool) {
+    let normal = Vector3::new(0.0, if up { 1.0 } else { -1.0 }, 0.0);
+    let center = corner(Vector3::new(0.0, y, 0.0), normal, [0.5, 0.5]);
+    let rim = |i: usize| {
+        let (sin, cos) = turn(i, segments).sin_cos();
+        let uv = [0.5 + 0.5 * sin, 0.5 + 0.5 * cos];
+        corner(Vector3::new(radius * sin, y, radius * cos), normal, uv)
+    };
+    for i in 0..segments {
+        if up {
+            triangle(builder, center, rim(i), rim(i + 1));
+        } else {
+            triangle(builder, center, rim(i + 1), rim(i));
+        }
+    }
+}
+
+// Angle of step `i` of `segments` around a circle. The last step is exactly
+// the first one, so vertices on texture seams share their position.
+fn turn(i: usize, segments: usize) -> f32 {
+    (i % segments) as f32 / segments as f32 * 2.0 * PI
+}
+
+// Unit vector at longitude `theta` and latitude `phi`, exactly on the Y axis
+// at the poles.
+fn spherical(theta: f32, phi: f32) -> Vector3<f32> {
+    let (sin, cos) = phi.sin_cos();
+    if cos.abs() < 1e-6 {
+        return Vector3::new(0.0, sin.signum(), 0.0);
+    }
+    Vector3::new(cos * theta.sin(), sin, cos * theta.cos())
+}
+
+fn icosahedron() -> (Vec<Vector3<f32>>, Vec<[u32; 3]>) {
+    let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+    let positions = vec![
+        Vector3::new(-1.0, t, 0.0),
+        Vector3::new(1.0, t, 0.0),
+        Vector3::new(-1.0, -t, 0.0),
+        Vector3::new(1.0, -t, 0.0),
+        Vector3::new(0.0, -1.0, t),
+        Vector3::new(0.0, 1.0, t),
+        Vector3::new(0.0, -1.0, -t),
+        Vector3::new(0.0, 1.0, -t),
+        Vector3::new(t, 0.0, -1.0),
+        Vector3::new(t, 0.0, 1.0),
+        Vector3::new(-t, 0.0, -1.0),
+        Vector3::new(-t, 0.0, 1.0),
+    ];
+    let faces = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+    (
+        positions.into_iter().map(|p| p.normalize()).collect(),
+        faces,
+    )
+}
+
+// Splits each triangle in 4, the new vertices pushed back on the unit sphere.
+fn subdivide(positions: &mut Vec<Vector3<f32>>, faces: &[[u32; 3]]) -> Vec<[u32; 3]> {
+    let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+    let mut midpoint = |a: u32, b: u32| {
+        let key = (a.min(b), a.max(b));
+        *midpoints.entry(key).or_insert_with(|| {
+            let position = (positions[a as usize] + positions[b as usize]).normalize();
+            positions.push(position);
+            (positions.len() - 1) as u32
+        })
+    };
+
+    let mut subdivided = Vec::with_capacity(faces.len() * 4);
+    for &[a, b, c] in faces {
+        let ab = midpoint(a, b);
+        let bc = midpoint(b, c);
+        let ca = midpoint(c, a);
+        subdivided.push([a, ab, ca]);
+        subdivided.push([b, bc, ab]);
+        subdivided.push([c, ca, bc]);
+        subdivided.push([ab, bc, ca]);
+    }
+    subdivided
+}
+
+impl MeshData {
+    // `segments` around Y, `rings` from pole to pole. The texture wraps once
+    // around, with a seam at -Z.
+    pub fn uv_sphere(radius: f32, segments: usize, rings: usize) -> MeshData {
+        if segments == 0 || rings == 0 {
+            return MeshData::default();
+        }
+        let mut builder = MeshBuilder::new();
+        grid(&mut builder, segments, rings, |i, j| {
+            let u = i as f32 / segments as f32;
+            let v = j as f32 / rings as f32;
+            let normal = spherical(turn(i, segments), v * PI - FRAC_PI_2);
+            corner(normal * radius, normal, [u, v])
+        });
+        builder.build()
+    }
+
+    // Icosahedron whose faces are split in 4 `subdivisions` times, for
+    // evenly spread vertices. Texture coordinates are spherical like
+    // `uv_sphere`, vertices on the seam are duplicated.
+    pub fn icosphere(radius: f32, subdivisions: usize) -> MeshData {
+        let (mut positions, mut faces) = icosahedron();
+        for _ in 0..subdivisions {
+            faces = subdivide(&mut positions, &faces);
+        }
+
+        let mut builder = MeshBuilder::new();
+        for face in &faces {
+            let normals = face.map(|index| positions[index as usize]);
+            let mut uv = normals.map(|n| {
+                let u = n.x.atan2(n.z) / (2.0 * PI);
+                [u.rem_euclid(1.0), 0.5 + n.y.clamp(-1.0, 1.0).asin() / PI]
+            });
+
+            // Triangles crossing the seam take u past 1 instead of wrapping.
+            let max_u = uv.iter().map(|uv| uv[0]).fold(0.0, f32::max);
+            for uv in uv.iter_mut() {
+                if max_u - uv[0] > 0.5 {
+                    uv[0] += 1.0;
+                }
+            }
+            // The longitude of the poles is the one of the opposite edge.
+            for k in 0..3 {
+                if normals[k].y.abs() > 0.9999 {
+                    uv[k][0] = (uv[(k + 1) % 3][0] + uv[(k + 2) % 3][0]) / 2.0;
+                }
+            }
+
+            let corners: Vec<Corner> = (0..3)
+                .map(|k| corner(normals[k] * radius, normals[k], uv[k]))
+                .collect();
+            builder.triangle(corners[0], corners[1], corners[2]);
+        }
+        builder.build()
+    }
+
+    // Plane in XZ facing +Y, split in `x_segments` by `z_segments` quads.
+    pub fn plane(width: f32, depth: f32, x_segments: usize, z_segments: usize) -> MeshData {
+        if x_segments == 0 || z_segments == 0 {
+            return MeshData::default();
+        }
+        let mut builder = MeshBuilder::new();
+        let normal = Vector3::new(0.0, 1.0, 0.0);
+        grid(&mut builder, x_segments, z_segments, |i, j| {
+            let u = i as f32 / x_segments as f32;
+            let v = j as f32 / z_segments as f32;
+            let position = Vector3::new((u - 0.5) * width, 0.0, (0.5 - v) * depth);
+            corner(position, normal, [u, v])
+        });
+        builder.build()
+    }
+
+    // Cylinder along Y with its caps.
+    pub fn cylinder(radius: f32, height: f32, segments: usize) -> MeshData {
+        if segments == 0 {
+            return MeshData::default();
+        }
+        let mut builder = MeshBuilder::new();
+        grid(&mut builder, segments, 1, |i, j| {
+            let u = i as f32 / segments as f32;
+            let normal = spherical(turn(i, segments), 0.0);
+            let position = normal * radius + Vector3::new(0.0, (j as f32 - 0.5) * height, 0.0);
+            corner(position, normal, [u, j as f32])
+        });
+        disk(&mut builder, radius, height / 2.0, segments, true);
+        disk(&mut builder, radius, -height / 2.0, segments, false);
+        builder.build()
+    }
+
+    // Cone along Y, apex up, with its base.
+    pub fn cone(radius: f32, height: f32, segments: usize) -> MeshData {
+        if segments == 0 {
+            return MeshData::default();
+        }
+        let mut builder = MeshBuilder::new();
+        grid(&mut builder, segments, 1, |i, j| {
+            let u = i as f32 / segments as f32;
+            let (sin, cos) = turn(i, segments).sin_cos();
+            let scale = 1.0 - j as f32;
+            let position = Vector3::new(
+                radius * sin * scale,
+                (j as f32 - 0.5) * height,
+                radius * cos * scale,
+            );
+            let normal = Vector3::new(height * sin, radius, height * cos);
+            corner(position, normal, [u, j as f32])
+        });
+        disk(&mut builder, radius, -height / 2.0, segments, false);
+        builder.build()
+    }
+
+    // Torus around Y: `major_segments` around the ring of radius `major`,
+    // `minor_segments` around the tube of radius `minor`.
+    pub fn torus(major: f32, minor: f32, major_segments: usize, minor_segments: usize) -> MeshData {
+        if major_segments == 0 || minor_segments == 0 {
+            return MeshData::default();
+        }
+        let mut builder = MeshBuilder::new();
+        grid(&mut builder, major_segments, minor_segments, |i, j| {
+            let u = i as f32 / major_segments as f32;
+            let v = j as f32 / minor_segments as f32;
+            let theta = turn(i, major_segments);
+            let center = spherical(theta, 0.0) * major;
+            let normal = spherical(theta, turn(j, minor_segments));
+            corner(center + normal * minor, normal, [u, v])
+        });
+        builder.build()
+    }
+
+    // Cylinder of `height` along Y closed by two hemispheres of `rings`
+    // rings, `height + 2 * radius` tall in total.
+    pub fn capsule(radius: f32, height: f32, segments: usize, rings: usize) -> MeshData {
+        if segments == 0 || rings == 0 {
+            return MeshData::default();
+        }
+        let mut builder = MeshBuilder::new();
+        let total = height + 2.0 * radius;
+        grid(&mut builder, segments, 2 * rings + 1, |i, j| {
+            let u = i as f32 / segments as f32;
+            // Rows 0 to rings are the bottom hemisphere, the next ones the top.
+            let (phi, offset) = if j <= rings {
+                (
+                    j as f32 / rings as f32 * FRAC_PI_2 - FRAC_PI_2,
+                    -height / 2.0,
+                )
+            } else {
+                (
+                    (j - rings - 1) as f32 / rings as f32 * FRAC_PI_2,
+                    height / 2.0,
+                )
+            };
+            let normal = spherical(turn(i, segments), phi);
+            let position = normal * radius + Vector3::new(0.0, offset, 0.0);
+            corner(position, normal, [u, position.y / total + 0.5])
+        });
+        builder.build()
+    }
+
+    // Single triangle covering the screen, the usual alternative to
+    // `fs_quad` without a diagonal seam.
+    pub fn fs_triangle() -> MeshData {
+        MeshData {
+            v_components: 2,
+            vertices: vec![-1.0, -1.0, 3.0, -1.0, -1.0, 3.0],
+            uv_components: 2,
+            uv: Some(vec![0.0, 0.0, 2.0, 0.0, 0.0, 2.0]),
+            ..MeshData::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Checks the counts, the indices and that every normal is unit length.
+    fn check(mesh: &MeshData, vertices: usize, indices: usize) {
+        mesh.check().unwrap();
+        assert_eq!(mesh.vertex_count(), vertices);
+        assert_eq!(mesh.element_count(), indices);
+        let normals = mesh.normals.as_ref().unwrap();
+        for normal in normals.chunks(3) {
+            let length = Vector3::new(normal[0], normal[1], normal[2]).magnitude();
+            assert!((length - 1.0).abs() <= 1e-5, "normal of length {}", length);
+        }
+    }
+
+    // Poles only keep the corners of their non degenerate triangles.
+    #[test]
+    fn uv_sphere() {
+        check(&MeshData::uv_sphere(1.0, 4, 3), 2 * 4 + 2 * 5, 6 * 4 * 2);
+        check(&MeshData::uv_sphere(2.0, 8, 2), 2 * 8 + 9, 6 * 8);
+    }
+
+    // 12 and 42 positions, plus the duplicates on the texture seam.
+    #[test]
+    fn icosphere() {
+        check(&MeshData::icosphere(1.0, 0), 12 + 2, 60);
+        check(&MeshData::icosphere(1.0, 1), 42 + 15, 240);
+    }
+
+    #[test]
+    fn plane() {
+        check(&MeshData::plane(1.0, 2.0, 1, 1), 4, 6);
+        check(&MeshData::plane(1.0, 2.0, 3, 2), 12, 36);
+    }
+
+    // The rim of the caps closes on its first vertex.
+    #[test]
+    fn cylinder() {
+        check(&MeshData::cylinder(1.0, 2.0, 3), 4 * 4, 12 * 3);
+        check(&MeshData::cylinder(1.0, 2.0, 8), 4 * 9, 12 * 8);
+    }
+
+    #[test]
+    fn cone() {
+        check(&MeshData::cone(1.0, 2.0, 3), 3 * 3 + 2, 6 * 3);
+        check(&MeshData::cone(1.0, 2.0, 8), 3 * 8 + 2, 6 * 8);
+    }
+
+    #[test]
+    fn torus() {
+        check(&MeshData::torus(1.0, 0.25, 3, 3), 16, 54);
+        check(&MeshData::torus(1.0, 0.25, 8, 4), 45, 192);
+    }
+
+    #[test]
+    fn capsule() {
+        check(&MeshData::capsule(0.5, 1.0, 4, 1), 2 * 4 + 2 * 5, 12 * 4);
+        check(
+            &MeshData::capsule(0.5, 1.0, 6, 2),
+            2 * 6 + 4 * 7,
+            12 * 6 * 2,
+        );
+    }
+
+    #[test]
+    fn fs_triangle() {
+        let mesh = MeshData::fs_triangle();
+        mesh.check().unwrap();
+        assert_eq!(mesh.vertex_count(), 3);
+        assert_eq!(mesh.element_count(), 3);
+    }
+
+    #[test]
+    fn zero_segments_give_empty_meshes() {
+        let meshes = [
+            MeshData::uv_sphere(1.0, 0, 4),
+            MeshData::uv_sphere(1.0, 4, 0),
+            MeshData::plane(1.0, 1.0, 0, 1),
+            MeshData::cylinder(1.0, 1.0, 0),
+            MeshData::cone(1.0, 1.0, 0),
+            MeshData::torus(1.0, 0.5, 4, 0),
+            MeshData::capsule(1.0, 1.0, 4, 0),
+        ];
+        for mesh in meshes.iter() {
+            mesh.check().unwrap();
+            assert_eq!(mesh.vertex_count(), 0);
+            assert_eq!(mesh.element_count(), 0);
+        }
+    }
+
+    fn distinct_positions(mesh: &MeshData) -> usize {
+        let positions: std::collections::HashSet<[u32; 3]> = mesh
+            .positions()
+            .iter()
+            .map(|position| {
+                // Adding 0 turns -0 into 0.
+                let position = position + Vector3::new(0.0, 0.0, 0.0);
+                [position.x, position.y, position.z].map(f32::to_bits)
+            })
+            .collect();
+        positions.len()
+    }
+
+    // Vertices duplicated on texture seams and poles are at exactly the same
+    // position, so that welding or smoothing them leaves no crack.
+    #[test]
+    fn seams_and_poles_share_positions() {
+        assert_eq!(
+            distinct_positions(&MeshData::uv_sphere(1.0, 8, 4)),
+            2 + 3 * 8
+        );
+        assert_eq!(
+            distinct_positions(&MeshData::capsule(0.5, 1.0, 6, 2)),
+            2 + 4 * 6
+        );
+        assert_eq!(distinct_positions(&MeshData::torus(1.0, 0.25, 8, 4)), 8 * 4);
+        assert_eq!(
+            distinct_positions(&MeshData::cylinder(1.0, 2.0, 8)),
+            2 * 8 + 2
+        );
+        assert_eq!(distinct_positions(&MeshData::icosphere(1.0, 1)), 42);
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Vector2, Vector3};
+
+use super::data::{MeshData, MeshError};
+
+// Part of `vector` orthogonal to the unit `normal`.
+fn orthogonal(vector: Vector3<f32>, normal: Vector3<f32>) -> Vector3<f32> {
+    vector - normal * normal.dot(vector)
+}
+
+fn normalize_or_zero(vector: Vector3<f32>) -> Vector3<f32> {
+    if vector.magnitude2() > 0.0 {
+        vector.normalize()
+    } else {
+        vector
+    }
+}
+
+// Any unit vector orthogonal to `normal`, for vertices whose faces have no
+// usable texture coordinates.
+fn any_tangent(normal: Vector3<f32>) -> Vector3<f32> {
+    let axis = if normal.x.abs() < 0.9 {
+        Vector3::new(1.0, 0.0, 0.0)
+    } else {
+        Vector3::new(0.0, 1.0, 0.0)
+    };
+    normalize_or_zero(orthogonal(axis, normal))
+}
+
+fn corner_angle(a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>) -> f32 {
+    let (ab, ac) = (b - a, c - a);
+    if ab.magnitude2() == 0.0 || ac.magnitude2() == 0.0 {
+        return 0.0;
+    }
+    ab.normalize().dot(ac.normalize()).clamp(-1.0, 1.0).acos()
+}
+
+// Directions in which u and v grow on a triangle, `None` when its texture
+// coordinates are degenerate.
+fn face_tangent(
+    positions: [Vector3<f32>; 3],
+    uv: [Vector2<f32>; 3],
+) -> Option<(Vector3<f32>, Vector3<f32>)> {
+    let (e1, e2) = (positions[1] - positions[0], positions[2] - positions[0]);
+    let (d1, d2) = (uv[1] - uv[0], uv[2] - uv[0]);
+    let area = d1.x * d2.y - d2.x * d1.y;
+    if area == 0.0 || !area.is_finite() {
+        return None;
+    }
+    Some((
+        (e1 * d2.y - e2 * d1.y) / area,
+        (e2 * d1.x - e1 * d2.x) / area,
+    ))
+}
+
+// Handedness of the tangent space of a corner, against the normal of its
+// vertex as MikkTSpace does, so that flipped normals flip it too.
+fn is_right_handed(normal: Vector3<f32>, tangent: Vector3<f32>, bitangent: Vector3<f32>) -> bool {
+    normal.cross(tangent).dot(bitangent) >= 0.0
+}
+
+impl MeshData {
+    // Per vertex tangents following the MikkTSpace conventions, for normal
+    // maps baked by most tools: the tangent is orthogonal to the normal,
+    // averaged over the faces around the vertex weighted by their angle, and
+    // its w is the handedness so that shaders rebuild the bitangent as
+    // `w * cross(normal, tangent.xyz)`. Vertices shared by faces of opposite
+    // handedness, e.g. on mirrored texture coordinates, are split. Needs
+    // normals and texture coordinates.
+    pub fn generate_tangents(&mut self) -> Result<(), MeshError> {
+        if self.interleaved.is_some() {
+            return Err(MeshError::Interleaved);
+        }
+        self.check()?;
+        let triangles = self.triangles()?;
+        let normals = self
+            .normals
+            .clone()
+            .ok_or_else(|| MeshError::Missing("normal".to_string()))?;
+        let uv = self
+            .uv
+            .as_ref()
+            .ok_or_else(|| MeshError::Missing("uv".to_string()))?;
+
+        let positions = self.positions();
+        let n_components = self.n_components as usize;
+        let normal = |vertex: u32| {
+            let start = vertex as usize * n_components;
+            let component = |index: usize| {
+                if index < n_components {
+                    normals[start + index]
+                } else {
+                    0.0
+                }
+            };
+            normalize_or_zero(Vector3::new(component(0), component(1), component(2)))
+        };
+        let texcoord = |vertex: u32| {
+            let start = vertex as usize * self.uv_components as usize;
+            let v = if self.uv_components > 1 {
+                uv[start + 1]
+            } else {
+                0.0
+            };
+            Vector2::new(uv[start], v)
+        };
+
+        let faces: Vec<_> = triangles
+            .iter()
+            .map(|triangle| {
+                face_tangent(
+                    triangle.map(|vertex| positions[vertex as usize]),
+                    triangle.map(texcoord),
+                )
+            })
+            .collect();
+
+        // Handedness of the corners: faces without usable texture coordinates
+        // take the one of the vertex' other faces.
+        let mut handedness: HashMap<u32, bool> = HashMap::new();
+        for (triangle, face) in triangles.iter().zip(&faces) {
+            if let Some((tangent, bitangent)) = face {
+                for vertex in triangle {
+                    handedness
+                        .entry(*vertex)
+                        .or_insert_with(|| is_right_handed(normal(*vertex), *tangent, *bitangent));
+                }
+            }
+        }
+
+        // A vertex keeps its index for its first handedness, and is copied
+        // at the end for the other one.
+        let vertex_count = self.vertex_count() as u32;
+        let mut split: HashMap<(u32, bool), u32> = HashMap::new();
+        let mut sources: Vec<u32> = (0..vertex_count).collect();
+        let mut sums: HashMap<u32, (Vector3<f32>, bool)> = HashMap::new();
+        let mut indices = Vec::with_capacity(triangles.len() * 3);
+
+        for (triangle, face) in triangles.iter().zip(&faces) {
+            for corner in 0..3 {
+                let vertex = triangle[corner];
+                let positive = match face {
+                    Some((tangent, bitangent)) => {
+                        is_right_handed(normal(vertex), *tangent, *bitangent)
+                    }
+                    None => handedness.get(&vertex).cloned().unwrap_or(true),
+                };
+                let first = handedness.get(&vertex).cloned().unwrap_or(true) == positive;
+                let index = *split.entry((vertex, positive)).or_insert_with(|| {
+                    if first {
+                        vertex
+                    } else {
+                        sources.push(vertex);
+                        (sources.len() - 1) as u32
+                    }
+                });
+                indices.push(index);
+
+                let sum = sums
+                    .entry(index)
+                    .or_insert((Vector3::new(0.0, 0.0, 0.0), positive));
+                if let Some((tangent, _)) = face {
+                    let n = normal(vertex);
+                    let p = triangle.map(|vertex| positions[vertex as usize]);
+                    let angle = corner_angle(p[corner], p[(corner + 1) % 3], p[(corner + 2) % 3]);
+                    sum.0 += normalize_or_zero(orthogonal(*tangent, n)) * angle;
+                }
+            }
+        }
+
+        if sources.len() > vertex_count as usize || self.indices.is_none() {
+            self.remap_vertices(&sources, indices);
+        }
+
+        let mut tangents = Vec::with_capacity(sources.len() * 4);
+        for (index, source) in sources.iter().enumerate() {
+            let n = normal(*source);
+            let (tangent, positive) = sums
+                .get(&(index as u32))
+                .cloned()
+                .unwrap_or((Vector3::new(0.0, 0.0, 0.0), true));
+            let tangent = normalize_or_zero(orthogonal(tangent, n));
+            let tangent = if tangent.magnitude2() > 0.0 {
+                tangent
+            } else {
+                any_tangent(n)
+            };
+            let w = if positive { 1.0 } else { -1.0 };
+            tangents.extend_from_slice(&[tangent.x, tangent.y, tangent.z, w]);
+        }
+
+        self.tangents = Some(tangents);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Quads of the XY plane facing +Z, textured by `u(x)` and v = y.
+    fn strip<F: Fn(f32) -> f32>(xs: &[f32], u: F) -> MeshData {
+        let mut vertices = Vec::new();
+        let mut normals = Vec::new();
+        let mut uv = Vec::new();
+        for x in xs {
+            for y in [0.0, 1.0] {
+                vertices.extend_from_slice(&[*x, y, 0.0]);
+                normals.extend_from_slice(&[0.0, 0.0, 1.0]);
+                uv.extend_from_slice(&[u(*x), y]);
+            }
+        }
+        let mut indices = Vec::new();
+        for quad in 0..xs.len() as u32 - 1 {
+            let (a, b) = (quad * 2, quad * 2 + 2);
+            indices.extend_from_slice(&[a, b, b + 1, a, b + 1, a + 1]);
+        }
+        MeshData {
+            vertices,
+            indices: Some(indices),
+            normals: Some(normals),
+            uv: Some(uv),
+            n_components: 3,
+            uv_components: 2,
+            ..MeshData::default()
+        }
+    }
+
+    fn tangent(mesh: &MeshData, vertex: usize) -> [f32; 4] {
+        let tangents = mesh.tangents.as_ref().unwrap();
+        [0, 1, 2, 3].map(|component| tangents[vertex * 4 + component])
+    }
+
+    #[test]
+    fn plane_tangents_follow_u() {
+        let mut plane = strip(&[0.0, 1.0], |x| x);
+        plane.generate_tangents().unwrap();
+        assert_eq!(plane.vertex_count(), 4);
+        for vertex in 0..4 {
+            assert_eq!(tangent(&plane, vertex), [1.0, 0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn flipped_normals_flip_the_handedness() {
+        let mut plane = strip(&[0.0, 1.0], |x| x);
+        for normal in plane.normals.as_mut().unwrap().chunks_mut(3) {
+            normal[2] = -1.0;
+        }
+        plane.generate_tangents().unwrap();
+        for vertex in 0..4 {
+            assert_eq!(tangent(&plane, vertex), [1.0, 0.0, 0.0, -1.0]);
+        }
+    }
+
+    // u goes back down on the left half, as when a texture is mirrored on
+    // both sides of a model.
+    #[test]
+    fn mirrored_uv_split_the_shared_vertices() {
+        let mut strip = strip(&[-1.0, 0.0, 1.0], f32::abs);
+        strip.generate_tangents().unwrap();
+        // The two vertices at x = 0 are duplicated.
+        assert_eq!(strip.vertex_count(), 8);
+
+        let positions = strip.positions();
+        for triangle in strip.triangles().unwrap() {
+            let left = triangle
+                .iter()
+                .any(|vertex| positions[*vertex as usize].x < 0.0);
+            let expected = if left {
+                [-1.0, 0.0, 0.0, -1.0]
+            } else {
+                [1.0, 0.0, 0.0, 1.0]
+            };
+            for vertex in triangle.iter() {
+                assert_eq!(tangent(&strip, *vertex as usize), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn tangents_are_orthogonal_to_the_normals() {
+        let mut sphere = MeshData::uv_sphere(1.0, 12, 8);
+        sphere.generate_tangents().unwrap();
+        let normals = sphere.normals.as_ref().unwrap();
+        for vertex in 0..sphere.vertex_count() {
+            let [x, y, z, w] = tangent(&sphere, vertex);
+            let n = Vector3::new(
+                normals[vertex * 3],
+                normals[vertex * 3 + 1],
+                normals[vertex * 3 + 2],
+            );
+            let t = Vector3::new(x, y, z);
+            assert!((t.magnitude() - 1.0).abs() < 1e-5);
+            assert!(t.dot(n).abs() < 1e-5);
+            assert!(w == 1.0 || w == -1.0);
+        }
+    }
+
+    #[test]
+    fn missing_attributes_are_errors() {
+        let mut mesh = strip(&[0.0, 1.0], |x| x);
+        mesh.uv = None;
+        mesh.uv_components = 0;
+        assert_eq!(
+            mesh.generate_tangents(),
+            Err(MeshError::Missing("uv".to_string()))
+        );
+
+        let mut mesh = strip(&[0.0, 1.0], |x| x);
+        mesh.normals = None;
+        mesh.n_components = 0;
+        assert_eq!(
+            mesh.generate_tangents(),
+            Err(MeshError::Missing("normal".to_string()))
+        );
+    }
+}
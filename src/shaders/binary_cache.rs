@@ -0,0 +1,185 @@
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::fs;
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::rc::Rc;
+
+use gl;
+
+use super::*;
+
+thread_local! {
+    static CACHE_DIR: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+// Once enabled, every linked program is saved with glGetProgramBinary in `dir`
+// and later links of the same sources are loaded back from there. This only
+// skips linking: the key is computed from the shaders given to
+// `Program::load_program`, which are already compiled by then.
+pub fn enable(dir: &Path) {
+    CACHE_DIR.with(|cache_dir| *cache_dir.borrow_mut() = Some(dir.to_path_buf()));
+}
+
+pub fn disable() {
+    CACHE_DIR.with(|cache_dir| *cache_dir.borrow_mut() = None);
+}
+
+pub fn cache_dir() -> Option<PathBuf> {
+    CACHE_DIR.with(|cache_dir| cache_dir.borrow().clone())
+}
+
+fn gl_string(name: u32) -> String {
+    unsafe {
+        let string = gl::GetString(name);
+        if string.is_null() {
+            return String::new();
+        }
+        CStr::from_ptr(string as *const _)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+// 64-bit FNV-1a. Unlike `DefaultHasher`, its output doesn't change between
+// Rust releases, which would orphan every cached binary.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    // Length prefixed, so consecutive fields can't run into each other.
+    fn field(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+}
+
+// A binary is only valid for the driver that produced it, so the vendor,
+// renderer and version strings are part of the key. The defines are already
+// part of the preprocessed source.
+pub fn cache_key(shaders: &[Rc<Shader>], separable: bool) -> String {
+    let mut hasher = Fnv1a::new();
+    hasher.write(&[separable as u8]);
+    for shader in shaders {
+        hasher.field(format!("{:?}", shader.shader_type).as_bytes());
+        hasher.field(shader.source.as_bytes());
+        if let Some(module) = &shader.spirv {
+            hasher.field(&module.binary);
+            hasher.field(module.specialization.entry_point.as_bytes());
+            let constants = &module.specialization.constants;
+            hasher.write(&(constants.len() as u64).to_le_bytes());
+            for (id, value) in constants {
+                hasher.write(&id.to_le_bytes());
+                hasher.write(&value.to_le_bytes());
+            }
+        }
+    }
+    hasher.field(gl_string(gl::VENDOR).as_bytes());
+    hasher.field(gl_string(gl::RENDERER).as_bytes());
+    hasher.field(gl_string(gl::VERSION).as_bytes());
+
+    format!("{:016x}", hasher.0)
+}
+
+fn cache_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("{}.bin", key))
+}
+
+// Returns a linked program, or `None` when there is no cached binary or when
+// the driver rejects it.
+pub fn load(dir: &Path, key: &str, separable: bool) -> Option<u32> {
+    let path = cache_path(dir, key);
+    let data = fs::read(&path).ok()?;
+    if data.len() < 4 {
+        return None;
+    }
+
+    let format = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let binary = &data[4..];
+
+    unsafe {
+        let addr = gl::CreateProgram();
+        if separable {
+            gl::ProgramParameteri(addr, gl::PROGRAM_SEPARABLE, i32::from(gl::TRUE));
+        }
+        gl::ProgramBinary(
+            addr,
+            format,
+            binary.as_ptr() as *const c_void,
+            binary.len() as i32,
+        );
+
+        let mut status: i32 = 0;
+        gl::GetProgramiv(addr, gl::LINK_STATUS, &mut status);
+        if status == i32::from(gl::FALSE) {
+            #[cfg(feature = "debug")]
+            eprintln!("[ERR] Cached program {} rejected", path.display());
+
+            gl::DeleteProgram(addr);
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+
+        Some(addr)
+    }
+}
+
+pub fn store(dir: &Path, key: &str, addr: u32) {
+    let mut length: i32 = 0;
+    let mut format: u32 = 0;
+    unsafe {
+        gl::GetProgramiv(addr, gl::PROGRAM_BINARY_LENGTH, &mut length);
+    }
+    if length <= 0 {
+        return;
+    }
+
+    let mut binary: Vec<u8> = vec![0; length as usize];
+    unsafe {
+        gl::GetProgramBinary(
+            addr,
+            length,
+            ptr::null_mut(),
+            &mut format,
+            binary.as_mut_ptr() as *mut c_void,
+        );
+    }
+
+    let mut data = format.to_le_bytes().to_vec();
+    data.extend_from_slice(&binary);
+
+    let path = cache_path(dir, key);
+    let written = fs::create_dir_all(dir).and_then(|_| fs::write(&path, &data));
+    if let Err(err) = written {
+        #[cfg(feature = "debug")]
+        eprintln!("[ERR] Couldn't cache program {} : {}", path.display(), err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_reference_values() {
+        let hash = |bytes: &[u8]| {
+            let mut hasher = Fnv1a::new();
+            hasher.write(bytes);
+            hasher.0
+        };
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+}
@@ -0,0 +1,207 @@
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::c_void;
+use std::path::Path;
+use std::rc::Rc;
+
+use gl;
+
+use super::*;
+
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Barrier {
+    STORAGE,
+    VERTEX,
+    UNIFORM,
+    BUFFER_UPDATE,
+    ALL,
+}
+
+impl Barrier {
+    fn gl_bits(self) -> u32 {
+        match self {
+            Barrier::STORAGE => gl::SHADER_STORAGE_BARRIER_BIT,
+            Barrier::VERTEX => gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT,
+            Barrier::UNIFORM => gl::UNIFORM_BARRIER_BIT,
+            Barrier::BUFFER_UPDATE => gl::BUFFER_UPDATE_BARRIER_BIT,
+            Barrier::ALL => gl::ALL_BARRIER_BITS,
+        }
+    }
+}
+
+pub fn memory_barrier(barriers: &[Barrier]) {
+    let bits = barriers
+        .iter()
+        .fold(0, |bits, barrier| bits | barrier.gl_bits());
+    unsafe {
+        gl::MemoryBarrier(bits);
+    }
+}
+
+pub fn group_count(count: usize, local_size: u32) -> u32 {
+    count.div_ceil(local_size.max(1) as usize) as u32
+}
+
+// A shader storage buffer holding `len` elements of `T`. `T` must match the
+// layout of the GLSL buffer element (e.g. `[f32; 4]` for a `vec4[]`).
+#[derive(Debug)]
+pub struct StorageBuffer<T: Copy> {
+    pub addr: u32,
+    len: usize,
+    marker: PhantomData<T>,
+}
+
+impl<T: Copy> Drop for StorageBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.addr);
+        }
+    }
+}
+
+impl<T: Copy> StorageBuffer<T> {
+    pub fn new(data: &[T]) -> StorageBuffer<T> {
+        let mut addr: u32 = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut addr);
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, addr);
+            gl::BufferData(
+                gl::SHADER_STORAGE_BUFFER,
+                mem::size_of_val(data) as isize,
+                data.as_ptr() as *const c_void,
+                gl::DYNAMIC_COPY,
+            );
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        }
+
+        StorageBuffer {
+            addr,
+            len: data.len(),
+            marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn bind(&self, binding: u32) {
+        unsafe {
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, self.addr);
+        }
+    }
+
+    pub fn update(&mut self, data: &[T]) {
+        unsafe {
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.addr);
+            if data.len() == self.len {
+                gl::BufferSubData(
+                    gl::SHADER_STORAGE_BUFFER,
+                    0,
+                    mem::size_of_val(data) as isize,
+                    data.as_ptr() as *const c_void,
+                );
+            } else {
+                gl::BufferData(
+                    gl::SHADER_STORAGE_BUFFER,
+                    mem::size_of_val(data) as isize,
+                    data.as_ptr() as *const c_void,
+                    gl::DYNAMIC_COPY,
+                );
+                self.len = data.len();
+            }
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        }
+    }
+
+    // Copies the buffer back to the CPU, waiting for previous shader writes.
+    pub fn read(&self) -> Vec<T> {
+        let mut data: Vec<T> = Vec::with_capacity(self.len);
+        memory_barrier(&[Barrier::BUFFER_UPDATE]);
+        unsafe {
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.addr);
+            gl::GetBufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                0,
+                (mem::size_of::<T>() * self.len) as isize,
+                data.as_mut_ptr() as *mut c_void,
+            );
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+            data.set_len(self.len);
+        }
+        data
+    }
+}
+
+#[derive(Debug)]
+pub struct ComputeProgram {
+    pub program: Program,
+    pub local_size: [u32; 3],
+}
+
+impl ComputeProgram {
+    pub fn load(path: &Path) -> Result<ComputeProgram, ShaderError> {
+        let shader = Shader::load_shader(path)?;
+        if shader.shader_type != ShaderType::COMPUTE {
+            return Err(ShaderError::UnexpectedStage {
+                path: path.to_path_buf(),
+                expected: ShaderType::COMPUTE,
+                found: shader.shader_type,
+            });
+        }
+
+        let program = Program::load_program(&[Rc::new(shader)])?;
+        Ok(ComputeProgram::from_program(program))
+    }
+
+    pub fn from_program(program: Program) -> ComputeProgram {
+        let mut local_size = [1i32; 3];
+        unsafe {
+            gl::GetProgramiv(
+                program.addr,
+                gl::COMPUTE_WORK_GROUP_SIZE,
+                local_size.as_mut_ptr(),
+            );
+        }
+
+        ComputeProgram {
+            program,
+            local_size: [
+                local_size[0] as u32,
+                local_size[1] as u32,
+                local_size[2] as u32,
+            ],
+        }
+    }
+
+    pub fn dispatch(&self, groups: [u32; 3]) {
+        self.program.bind();
+        unsafe {
+            gl::DispatchCompute(groups[0], groups[1], groups[2]);
+        }
+    }
+
+    // Dispatches enough groups along x for one invocation per element.
+    pub fn dispatch_for(&self, count: usize) {
+        self.dispatch([group_count(count, self.local_size[0]), 1, 1]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_count_rounds_up() {
+        assert_eq!(group_count(256, 64), 4);
+        assert_eq!(group_count(257, 64), 5);
+        assert_eq!(group_count(1, 64), 1);
+        assert_eq!(group_count(0, 64), 0);
+        assert_eq!(group_count(10, 0), 10);
+    }
+}
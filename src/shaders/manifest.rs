@@ -0,0 +1,316 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use cgmath::{Vector2, Vector3, Vector4};
+
+use crate::utils;
+
+use super::preprocessor::Preprocessor;
+use super::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UniformValue {
+    Float(f32),
+    Int(i32),
+    UInt(u32),
+    Bool(bool),
+    Vec2(Vector2<f32>),
+    Vec3(Vector3<f32>),
+    Vec4(Vector4<f32>),
+    Sampler(u32),
+}
+
+// A program described by a `.prog` file, one directive per line:
+//
+//     # comment
+//     vertex   projection.vs
+//     fragment phong/phong.fs
+//     define   NAME value
+//     uniform  vec3 color 1.0 0.5 0.0
+//     separable
+//
+// The stages are vertex, fragment, geometry, compute, tess_control and
+// tess_evaluation, their paths are relative to the manifest and they are
+// compiled as declared whatever their extension (`.spv` SPIR-V modules are
+// loaded with their default specialization). A `#` only starts a comment at
+// the start of a line or after whitespace. Uniform types are float, int,
+// uint, bool, vec2, vec3, vec4 and sampler (whose value is a texture unit).
+#[derive(Debug, Clone, Default)]
+pub struct ProgramManifest {
+    pub stages: Vec<(ShaderType, PathBuf)>,
+    pub defines: Vec<(String, String)>,
+    pub uniforms: Vec<(String, UniformValue)>,
+    pub separable: bool,
+}
+
+fn parse_numbers<T: std::str::FromStr>(values: &[&str], count: usize) -> Option<Vec<T>> {
+    if values.len() != count {
+        return None;
+    }
+    values.iter().map(|value| value.parse().ok()).collect()
+}
+
+fn parse_uniform_value(kind: &str, values: &[&str]) -> Option<UniformValue> {
+    match kind {
+        "float" => parse_numbers(values, 1).map(|v| UniformValue::Float(v[0])),
+        "int" => parse_numbers(values, 1).map(|v| UniformValue::Int(v[0])),
+        "uint" => parse_numbers(values, 1).map(|v| UniformValue::UInt(v[0])),
+        "bool" => parse_numbers(values, 1).map(|v| UniformValue::Bool(v[0])),
+        "sampler" => parse_numbers(values, 1).map(|v| UniformValue::Sampler(v[0])),
+        "vec2" => parse_numbers(values, 2).map(|v| UniformValue::Vec2(Vector2::new(v[0], v[1]))),
+        "vec3" => {
+            parse_numbers(values, 3).map(|v| UniformValue::Vec3(Vector3::new(v[0], v[1], v[2])))
+        }
+        "vec4" => parse_numbers(values, 4)
+            .map(|v| UniformValue::Vec4(Vector4::new(v[0], v[1], v[2], v[3]))),
+        _ => None,
+    }
+}
+
+// A `#` starts a comment at the start of a line or after whitespace, so paths
+// and values like `a#b` are kept.
+fn strip_comment(line: &str) -> &str {
+    let mut previous = None;
+    for (index, c) in line.char_indices() {
+        if c == '#' && previous.is_none_or(char::is_whitespace) {
+            return &line[..index];
+        }
+        previous = Some(c);
+    }
+    line
+}
+
+impl ProgramManifest {
+    pub fn parse(src: &str, path: &Path) -> Result<ProgramManifest, ShaderError> {
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut manifest = ProgramManifest::default();
+
+        for (number, line) in src.lines().enumerate() {
+            let error = |message: &str| ShaderError::Manifest {
+                path: path.to_path_buf(),
+                line: number + 1,
+                message: message.to_string(),
+            };
+
+            let line = strip_comment(line).trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+
+            if let Some(stage) = get_shader_type_from_name(words[0]) {
+                if words.len() != 2 {
+                    return Err(error("expected a single shader path"));
+                }
+                manifest.stages.push((stage, dir.join(words[1])));
+                continue;
+            }
+
+            match words[0] {
+                "separable" => manifest.separable = true,
+                "define" => {
+                    if words.len() < 2 {
+                        return Err(error("expected a define name"));
+                    }
+                    let value = words[2..].join(" ");
+                    manifest.defines.push((words[1].to_string(), value));
+                }
+                "uniform" => {
+                    if words.len() < 4 {
+                        return Err(error("expected a uniform type, name and value"));
+                    }
+                    let value = parse_uniform_value(words[1], &words[3..])
+                        .ok_or_else(|| error("invalid uniform type or value"))?;
+                    manifest.uniforms.push((words[2].to_string(), value));
+                }
+                directive => {
+                    return Err(error(&format!("unknown directive {}", directive)));
+                }
+            }
+        }
+
+        if manifest.stages.is_empty() {
+            return Err(ShaderError::Manifest {
+                path: path.to_path_buf(),
+                line: 0,
+                message: "no shader stage".to_string(),
+            });
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn load(path: &Path) -> Result<ProgramManifest, ShaderError> {
+        let src = utils::load_file(path).map_err(|error| ShaderError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        ProgramManifest::parse(&src, path)
+    }
+
+    pub fn preprocessor(&self) -> Preprocessor {
+        self.defines
+            .iter()
+            .fold(Preprocessor::default(), |preprocessor, (name, value)| {
+                preprocessor.with_define(name, value)
+            })
+    }
+}
+
+impl Program {
+    pub fn load_manifest(path: &Path) -> Result<Program, ShaderError> {
+        #[cfg(feature = "debug")]
+        println!("[NFO] Loading program {}", path.display());
+
+        let manifest = ProgramManifest::load(path)?;
+        let preprocessor = manifest.preprocessor();
+
+        let mut shaders = Vec::with_capacity(manifest.stages.len());
+        for (stage, stage_path) in &manifest.stages {
+            let shader = Shader::load_shader_as(stage_path, *stage, &preprocessor)?;
+            shaders.push(Rc::new(shader));
+        }
+
+        let mut program = if manifest.separable {
+            Program::load_separable(&shaders)?
+        } else {
+            Program::load_program(&shaders)?
+        };
+        program.manifest = Some(path.to_string_lossy().into_owned());
+        program.default_uniforms = manifest.uniforms;
+        program.apply_default_uniforms()?;
+
+        Ok(program)
+    }
+}
+
+// Programs loaded from `<root>/<name>.prog`, so scenes can refer to them by
+// name. Each program is loaded once and shared.
+#[derive(Debug)]
+pub struct ProgramLibrary {
+    pub root: PathBuf,
+    programs: HashMap<String, Rc<RefCell<Program>>>,
+}
+
+impl ProgramLibrary {
+    pub fn new(root: &Path) -> ProgramLibrary {
+        ProgramLibrary {
+            root: root.to_path_buf(),
+            programs: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, name: &str) -> Result<Rc<RefCell<Program>>, ShaderError> {
+        if let Some(program) = self.programs.get(name) {
+            return Ok(Rc::clone(program));
+        }
+
+        let path = self.root.join(format!("{}.prog", name));
+        let program = Rc::new(RefCell::new(Program::load_manifest(&path)?));
+        self.programs.insert(name.to_string(), Rc::clone(&program));
+        Ok(program)
+    }
+
+    pub fn programs(&self) -> impl Iterator<Item = &Rc<RefCell<Program>>> {
+        self.programs.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(src: &str) -> Result<ProgramManifest, ShaderError> {
+        ProgramManifest::parse(src, Path::new("programs/test.prog"))
+    }
+
+    fn error_line(result: Result<ProgramManifest, ShaderError>) -> usize {
+        match result {
+            Err(ShaderError::Manifest { line, .. }) => line,
+            other => panic!("expected a manifest error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn full_manifest() {
+        let manifest = parse(
+            "# phong\n\
+             vertex   projection.vs\n\
+             fragment phong/phong.fs   # lit\n\
+             \n\
+             define   LIGHTS 4\n\
+             define   SHADOWS\n\
+             uniform  vec3 color 1.0 0.5 0.0\n\
+             uniform  sampler diffuse 2\n\
+             uniform  bool gamma true\n\
+             separable\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            manifest.stages,
+            vec![
+                (ShaderType::VERTEX, PathBuf::from("programs/projection.vs")),
+                (
+                    ShaderType::FRAGMENT,
+                    PathBuf::from("programs/phong/phong.fs")
+                ),
+            ]
+        );
+        assert_eq!(
+            manifest.defines,
+            vec![
+                ("LIGHTS".to_string(), "4".to_string()),
+                ("SHADOWS".to_string(), String::new()),
+            ]
+        );
+        assert_eq!(
+            manifest.uniforms,
+            vec![
+                (
+                    "color".to_string(),
+                    UniformValue::Vec3(Vector3::new(1.0, 0.5, 0.0))
+                ),
+                ("diffuse".to_string(), UniformValue::Sampler(2)),
+                ("gamma".to_string(), UniformValue::Bool(true)),
+            ]
+        );
+        assert!(manifest.separable);
+        assert_eq!(manifest.preprocessor().defines, manifest.defines);
+    }
+
+    #[test]
+    fn hash_inside_a_value_is_not_a_comment() {
+        let manifest = parse("vertex take#2.vs\ndefine TAG v1#2 # comment\n#define X 1\n").unwrap();
+        assert_eq!(
+            manifest.stages,
+            vec![(ShaderType::VERTEX, PathBuf::from("programs/take#2.vs"))]
+        );
+        assert_eq!(
+            manifest.defines,
+            vec![("TAG".to_string(), "v1#2".to_string())]
+        );
+    }
+
+    #[test]
+    fn unknown_directives_and_stages_are_errors() {
+        assert_eq!(error_line(parse("vertex a.vs\nshading phong\n")), 2);
+        assert_eq!(error_line(parse("vertex a.vs\npixel a.fs\n")), 2);
+        assert_eq!(error_line(parse("vertex a.vs b.vs\n")), 1);
+        assert_eq!(error_line(parse("# empty\n")), 0);
+    }
+
+    #[test]
+    fn bad_uniform_values_report_their_line() {
+        assert_eq!(error_line(parse("vertex a.vs\n\nuniform float f\n")), 3);
+        assert_eq!(error_line(parse("vertex a.vs\nuniform float f one\n")), 2);
+        assert_eq!(error_line(parse("vertex a.vs\nuniform vec3 v 1 2\n")), 2);
+        assert_eq!(
+            error_line(parse("vertex a.vs\nuniform mat2 m 1 0 0 1\n")),
+            2
+        );
+        assert_eq!(error_line(parse("vertex a.vs\nuniform sampler s -1\n")), 2);
+    }
+}
@@ -1,32 +1,223 @@
+pub mod binary_cache;
+pub mod compute;
+pub mod manifest;
+pub mod pipeline;
+pub mod preprocessor;
+pub mod reflection;
 pub mod shader_loader;
+pub mod spirv;
+pub mod std140;
+pub mod uniform_buffer;
+pub mod uniform_cache;
+pub mod validation;
+pub mod variants;
+pub mod watcher;
 
 use std::collections::HashMap;
-use std::path::Path;
+use std::error::Error;
+use std::ffi::NulError;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use gl;
 
-#[derive(Debug)]
+use manifest::UniformValue;
+use preprocessor::Preprocessor;
+use reflection::{ActiveAttribute, ActiveUniform, UniformType};
+use spirv::SpirvModule;
+use uniform_cache::UniformCache;
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ShaderType {
     VERTEX,
     FRAGMENT,
     GEOMETRY,
     COMPUTE,
+    TESS_CONTROL,
+    TESS_EVALUATION,
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    UnknownExtension(PathBuf),
+    Include {
+        path: PathBuf,
+        line: usize,
+        include: String,
+    },
+    IncludeCycle(Vec<PathBuf>),
+    Manifest {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    UnexpectedStage {
+        path: PathBuf,
+        expected: ShaderType,
+        found: ShaderType,
+    },
+    Compile {
+        path: PathBuf,
+        log: String,
+    },
+    Link {
+        log: String,
+    },
+    NotSeparable,
+    SpirvUnsupported,
+    Uniform(UniformError),
+    Nul(NulError),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::Io { path, error } => {
+                write!(f, "couldn't read shader {}: {}", path.display(), error)
+            }
+            ShaderError::UnknownExtension(path) => {
+                write!(f, "couldn't detect shader type for {}", path.display())
+            }
+            ShaderError::Include {
+                path,
+                line,
+                include,
+            } => write!(
+                f,
+                "{}:{}: couldn't find included file \"{}\"",
+                path.display(),
+                line,
+                include
+            ),
+            ShaderError::IncludeCycle(chain) => {
+                let chain: Vec<String> = chain
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                write!(f, "include cycle: {}", chain.join(" -> "))
+            }
+            ShaderError::Manifest {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            ShaderError::UnexpectedStage {
+                path,
+                expected,
+                found,
+            } => write!(
+                f,
+                "shader {} is a {:?} shader, expected a {:?} shader",
+                path.display(),
+                found,
+                expected
+            ),
+            ShaderError::Compile { path, log } => {
+                write!(f, "couldn't compile shader {}:\n{}", path.display(), log)
+            }
+            ShaderError::Link { log } => write!(f, "couldn't link program:\n{}", log),
+            ShaderError::NotSeparable => write!(f, "program wasn't linked as separable"),
+            ShaderError::SpirvUnsupported => {
+                write!(f, "SPIR-V shaders need OpenGL 4.6 or GL_ARB_gl_spirv")
+            }
+            ShaderError::Uniform(error) => write!(f, "{}", error),
+            ShaderError::Nul(error) => write!(f, "shader source contains a nul byte: {}", error),
+        }
+    }
+}
+
+impl Error for ShaderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ShaderError::Io { error, .. } => Some(error),
+            ShaderError::Uniform(error) => Some(error),
+            ShaderError::Nul(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<UniformError> for ShaderError {
+    fn from(error: UniformError) -> ShaderError {
+        ShaderError::Uniform(error)
+    }
+}
+
+impl From<NulError> for ShaderError {
+    fn from(error: NulError) -> ShaderError {
+        ShaderError::Nul(error)
+    }
 }
 
+#[derive(Debug)]
+pub enum UniformError {
+    Missing(String),
+    TypeMismatch {
+        name: String,
+        expected: UniformType,
+        found: UniformType,
+    },
+    ArrayOverflow {
+        name: String,
+        size: usize,
+        count: usize,
+    },
+}
+
+impl fmt::Display for UniformError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UniformError::Missing(name) => write!(f, "no active uniform named {}", name),
+            UniformError::TypeMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "uniform {} is a {:?}, tried to set it as a {:?}",
+                name, found, expected
+            ),
+            UniformError::ArrayOverflow { name, size, count } => write!(
+                f,
+                "uniform {} holds {} elements, tried to set {}",
+                name, size, count
+            ),
+        }
+    }
+}
+
+impl Error for UniformError {}
+
 #[derive(Debug)]
 pub struct Shader {
     pub addr: u32,
     pub path: String,
-    pub uniforms: Vec<String>,
+    pub source: String,
+    pub includes: Vec<String>,
     pub shader_type: ShaderType,
+    pub explicit_type: bool,
+    pub preprocessor: Preprocessor,
+    pub spirv: Option<SpirvModule>,
 }
 
 #[derive(Debug)]
 pub struct Program {
     pub addr: u32,
     pub shaders: Vec<Rc<Shader>>,
-    pub uniforms_location: HashMap<String, i32>,
+    pub uniforms: HashMap<String, ActiveUniform>,
+    pub attributes: HashMap<String, ActiveAttribute>,
+    pub separable: bool,
+    pub skip_missing_uniforms: bool,
+    pub uniform_cache: UniformCache,
+    pub manifest: Option<String>,
+    pub default_uniforms: Vec<(String, UniformValue)>,
 }
 
 impl Drop for Shader {
@@ -45,23 +236,66 @@ impl Drop for Program {
     }
 }
 
+// SPIR-V modules are named after their source, e.g. `phong.frag.spv`.
 pub fn get_shader_type(path: &Path) -> Option<ShaderType> {
     let ext = path.extension().and_then(|extension| extension.to_str());
     match ext {
-        Some("vs") => Some(ShaderType::VERTEX),
-        Some("fs") => Some(ShaderType::FRAGMENT),
-        Some("gs") => Some(ShaderType::GEOMETRY),
-        Some("cs") => Some(ShaderType::COMPUTE),
+        Some("spv") => path
+            .file_stem()
+            .and_then(|stem| get_shader_type(Path::new(stem))),
+        Some("vs") | Some("vert") => Some(ShaderType::VERTEX),
+        Some("fs") | Some("frag") => Some(ShaderType::FRAGMENT),
+        Some("gs") | Some("geom") => Some(ShaderType::GEOMETRY),
+        Some("cs") | Some("comp") => Some(ShaderType::COMPUTE),
+        Some("tcs") | Some("tesc") => Some(ShaderType::TESS_CONTROL),
+        Some("tes") | Some("tese") => Some(ShaderType::TESS_EVALUATION),
+        _ => None,
+    }
+}
+
+pub fn get_shader_type_from_name(name: &str) -> Option<ShaderType> {
+    match name {
+        "vertex" => Some(ShaderType::VERTEX),
+        "fragment" => Some(ShaderType::FRAGMENT),
+        "geometry" => Some(ShaderType::GEOMETRY),
+        "compute" => Some(ShaderType::COMPUTE),
+        "tess_control" => Some(ShaderType::TESS_CONTROL),
+        "tess_evaluation" => Some(ShaderType::TESS_EVALUATION),
         _ => None,
     }
 }
 
+// Stage declared in the source with `#pragma stage vertex` (or
+// `#pragma stage(vertex)`), used for extensions like `.glsl` that don't tell.
+pub fn get_pragma_shader_type(src: &str) -> Option<ShaderType> {
+    src.lines().find_map(|line| {
+        let rest = line.trim_start().strip_prefix('#')?.trim_start();
+        let rest = rest.strip_prefix("pragma")?.trim_start();
+        let name = rest.strip_prefix("stage")?;
+        let name = name.trim_matches(|c: char| c.is_whitespace() || c == '(' || c == ')');
+        get_shader_type_from_name(name)
+    })
+}
+
 pub fn get_gl_shader_type(shader_type: &Option<ShaderType>) -> Option<u32> {
     match shader_type {
         Some(ShaderType::VERTEX) => Some(gl::VERTEX_SHADER),
         Some(ShaderType::FRAGMENT) => Some(gl::FRAGMENT_SHADER),
         Some(ShaderType::GEOMETRY) => Some(gl::GEOMETRY_SHADER),
         Some(ShaderType::COMPUTE) => Some(gl::COMPUTE_SHADER),
+        Some(ShaderType::TESS_CONTROL) => Some(gl::TESS_CONTROL_SHADER),
+        Some(ShaderType::TESS_EVALUATION) => Some(gl::TESS_EVALUATION_SHADER),
         _ => None,
     }
 }
+
+pub fn get_gl_shader_stage_bit(shader_type: ShaderType) -> u32 {
+    match shader_type {
+        ShaderType::VERTEX => gl::VERTEX_SHADER_BIT,
+        ShaderType::FRAGMENT => gl::FRAGMENT_SHADER_BIT,
+        ShaderType::GEOMETRY => gl::GEOMETRY_SHADER_BIT,
+        ShaderType::COMPUTE => gl::COMPUTE_SHADER_BIT,
+        ShaderType::TESS_CONTROL => gl::TESS_CONTROL_SHADER_BIT,
+        ShaderType::TESS_EVALUATION => gl::TESS_EVALUATION_SHADER_BIT,
+    }
+}
@@ -0,0 +1,270 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use cgmath::{Matrix2, Matrix3, Matrix4, Vector2, Vector3, Vector4};
+
+use gl;
+
+use super::manifest::UniformValue;
+use super::*;
+
+const STAGES: [ShaderType; 6] = [
+    ShaderType::VERTEX,
+    ShaderType::TESS_CONTROL,
+    ShaderType::TESS_EVALUATION,
+    ShaderType::GEOMETRY,
+    ShaderType::FRAGMENT,
+    ShaderType::COMPUTE,
+];
+
+// Mixes the stages of separable programs, e.g. one vertex program shared by a
+// phong and a flat fragment program, without relinking anything. The programs
+// are shared with the `ShaderWatcher`, and a stage whose program was relinked
+// by a hot reload is bound again on the next `bind`.
+#[derive(Debug)]
+pub struct ProgramPipeline {
+    pub addr: u32,
+    // Programs running at least one stage, derived from `stages`.
+    pub programs: Vec<Rc<RefCell<Program>>>,
+    pub skip_missing_uniforms: bool,
+    // Program running each stage, in `STAGES` order.
+    stages: [Option<Rc<RefCell<Program>>>; 6],
+    // Address of the program each stage was last bound to, 0 when empty.
+    bound: Cell<[u32; 6]>,
+}
+
+impl Drop for ProgramPipeline {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgramPipelines(1, &self.addr);
+        }
+    }
+}
+
+impl Default for ProgramPipeline {
+    fn default() -> ProgramPipeline {
+        ProgramPipeline::new()
+    }
+}
+
+fn stage_index(shader_type: ShaderType) -> usize {
+    match shader_type {
+        ShaderType::VERTEX => 0,
+        ShaderType::TESS_CONTROL => 1,
+        ShaderType::TESS_EVALUATION => 2,
+        ShaderType::GEOMETRY => 3,
+        ShaderType::FRAGMENT => 4,
+        ShaderType::COMPUTE => 5,
+    }
+}
+
+impl ProgramPipeline {
+    pub fn new() -> ProgramPipeline {
+        let mut addr: u32 = 0;
+        unsafe {
+            gl::GenProgramPipelines(1, &mut addr);
+        }
+
+        ProgramPipeline {
+            addr,
+            programs: Vec::new(),
+            skip_missing_uniforms: true,
+            stages: Default::default(),
+            bound: Cell::new([0; 6]),
+        }
+    }
+
+    // Uses every stage of `program`, replacing the programs previously bound
+    // to those stages. A replaced program keeps running its other stages.
+    pub fn use_stages(&mut self, program: &Rc<RefCell<Program>>) -> Result<(), ShaderError> {
+        if !program.borrow().separable {
+            return Err(ShaderError::NotSeparable);
+        }
+
+        let bits = program.borrow().stage_bits();
+        for (stage, owner) in STAGES.iter().zip(self.stages.iter_mut()) {
+            if bits & get_gl_shader_stage_bit(*stage) != 0 {
+                *owner = Some(Rc::clone(program));
+            }
+        }
+        self.update_programs();
+        self.update_stages();
+        Ok(())
+    }
+
+    pub fn clear_stages(&mut self, shader_type: ShaderType) {
+        self.stages[stage_index(shader_type)] = None;
+        self.update_programs();
+        self.update_stages();
+    }
+
+    pub fn program_for(&self, shader_type: ShaderType) -> Option<&Rc<RefCell<Program>>> {
+        self.stages[stage_index(shader_type)].as_ref()
+    }
+
+    // A program is dropped once it runs no stage anymore.
+    fn update_programs(&mut self) {
+        let mut programs: Vec<Rc<RefCell<Program>>> = Vec::new();
+        for program in self.stages.iter().flatten() {
+            if !programs.iter().any(|other| Rc::ptr_eq(other, program)) {
+                programs.push(Rc::clone(program));
+            }
+        }
+        self.programs = programs;
+    }
+
+    // Binds the stages whose program changed since they were last bound.
+    fn update_stages(&self) {
+        let mut bound = self.bound.get();
+        for (index, stage) in STAGES.iter().enumerate() {
+            let addr = self.stages[index]
+                .as_ref()
+                .map_or(0, |program| program.borrow().addr);
+            if bound[index] != addr {
+                unsafe {
+                    gl::UseProgramStages(self.addr, get_gl_shader_stage_bit(*stage), addr);
+                }
+                bound[index] = addr;
+            }
+        }
+        self.bound.set(bound);
+    }
+
+    pub fn bind(&self) {
+        self.update_stages();
+        unsafe {
+            gl::UseProgram(0);
+            gl::BindProgramPipeline(self.addr);
+        }
+    }
+
+    pub fn unbind() {
+        unsafe {
+            gl::BindProgramPipeline(0);
+        }
+    }
+
+    // Sets the uniform in every stage program declaring it. The pipeline has
+    // to be bound, glUniform* then targets the active program of the pipeline.
+    pub fn set_with<F>(&self, name: &str, set: F) -> Result<(), UniformError>
+    where
+        F: Fn(&Program) -> Result<(), UniformError>,
+    {
+        let mut found = false;
+        for program in &self.programs {
+            let program = program.borrow();
+            if program.uniforms.contains_key(name) {
+                found = true;
+                unsafe {
+                    gl::ActiveShaderProgram(self.addr, program.addr);
+                }
+                set(&program)?;
+            }
+        }
+
+        if !found && !self.skip_missing_uniforms {
+            return Err(UniformError::Missing(name.to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn set_uniform(&self, name: &str, value: &UniformValue) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_uniform(name, value))
+    }
+
+    pub fn set_float(&self, name: &str, value: f32) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_float(name, value))
+    }
+
+    pub fn set_int(&self, name: &str, value: i32) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_int(name, value))
+    }
+
+    pub fn set_uint(&self, name: &str, value: u32) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_uint(name, value))
+    }
+
+    pub fn set_bool(&self, name: &str, value: bool) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_bool(name, value))
+    }
+
+    pub fn set_sampler(&self, name: &str, unit: u32) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_sampler(name, unit))
+    }
+
+    pub fn set_vec2(&self, name: &str, value: &Vector2<f32>) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_vec2(name, value))
+    }
+
+    pub fn set_vec3(&self, name: &str, value: &Vector3<f32>) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_vec3(name, value))
+    }
+
+    pub fn set_vec4(&self, name: &str, value: &Vector4<f32>) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_vec4(name, value))
+    }
+
+    pub fn set_ivec2(&self, name: &str, value: &Vector2<i32>) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_ivec2(name, value))
+    }
+
+    pub fn set_ivec3(&self, name: &str, value: &Vector3<i32>) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_ivec3(name, value))
+    }
+
+    pub fn set_ivec4(&self, name: &str, value: &Vector4<i32>) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_ivec4(name, value))
+    }
+
+    pub fn set_mat2(&self, name: &str, value: &Matrix2<f32>) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_mat2(name, value))
+    }
+
+    pub fn set_mat3(&self, name: &str, value: &Matrix3<f32>) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_mat3(name, value))
+    }
+
+    pub fn set_mat4(&self, name: &str, value: &Matrix4<f32>) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_mat4(name, value))
+    }
+
+    pub fn set_float_array(&self, name: &str, values: &[f32]) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_float_array(name, values))
+    }
+
+    pub fn set_int_array(&self, name: &str, values: &[i32]) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_int_array(name, values))
+    }
+
+    pub fn set_vec2_array(&self, name: &str, values: &[Vector2<f32>]) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_vec2_array(name, values))
+    }
+
+    pub fn set_vec3_array(&self, name: &str, values: &[Vector3<f32>]) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_vec3_array(name, values))
+    }
+
+    pub fn set_vec4_array(&self, name: &str, values: &[Vector4<f32>]) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_vec4_array(name, values))
+    }
+
+    pub fn set_mat4_array(&self, name: &str, values: &[Matrix4<f32>]) -> Result<(), UniformError> {
+        self.set_with(name, |program| program.set_mat4_array(name, values))
+    }
+
+    pub fn bind_uniform_block(&self, name: &str, binding: u32) -> Result<(), UniformError> {
+        let mut found = false;
+        for program in &self.programs {
+            let program = program.borrow();
+            if program.has_uniform_block(name) {
+                found = true;
+                program.bind_uniform_block(name, binding)?;
+            }
+        }
+
+        if !found && !self.skip_missing_uniforms {
+            return Err(UniformError::Missing(name.to_string()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,424 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::utils;
+
+use super::ShaderError;
+
+#[derive(Debug, Clone)]
+pub struct Preprocessor {
+    pub include_roots: Vec<PathBuf>,
+    pub defines: Vec<(String, String)>,
+    pub prelude: String,
+    pub epilogue: String,
+}
+
+impl Default for Preprocessor {
+    fn default() -> Preprocessor {
+        Preprocessor {
+            include_roots: vec![PathBuf::from("data/shaders")],
+            defines: Vec::new(),
+            prelude: String::new(),
+            epilogue: String::new(),
+        }
+    }
+}
+
+// Maps every line of the preprocessed source back to the file and line it
+// came from. The first file is always the shader itself.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    pub files: Vec<PathBuf>,
+    lines: Vec<(usize, usize)>,
+}
+
+#[derive(Debug)]
+pub struct PreprocessedSource {
+    pub source: String,
+    pub source_map: SourceMap,
+}
+
+fn parse_include(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start();
+    let rest = rest.strip_prefix("include")?.trim();
+    if rest.len() >= 2
+        && ((rest.starts_with('"') && rest.ends_with('"'))
+            || (rest.starts_with('<') && rest.ends_with('>')))
+    {
+        Some(&rest[1..rest.len() - 1])
+    } else {
+        None
+    }
+}
+
+fn is_pragma_once(line: &str) -> bool {
+    match line.trim_start().strip_prefix('#') {
+        Some(rest) => rest.split_whitespace().eq(["pragma", "once"]),
+        None => false,
+    }
+}
+
+// Whether a `/* */` comment is still open at the end of `line`.
+fn ends_in_comment(line: &str, mut in_comment: bool) -> bool {
+    let mut rest = line;
+    loop {
+        if in_comment {
+            match rest.find("*/") {
+                Some(end) => {
+                    rest = &rest[end + 2..];
+                    in_comment = false;
+                }
+                None => return true,
+            }
+        } else {
+            let line_comment = rest.find("//");
+            match rest.find("/*") {
+                Some(start) if line_comment.is_none_or(|slashes| start < slashes) => {
+                    rest = &rest[start + 2..];
+                    in_comment = true;
+                }
+                _ => return false,
+            }
+        }
+    }
+}
+
+impl SourceMap {
+    fn push(&mut self, file: usize, line: usize) {
+        self.lines.push((file, line));
+    }
+
+    // `line` is 1-based, as reported by the GLSL compilers.
+    pub fn resolve(&self, line: usize) -> Option<(&Path, usize)> {
+        let &(file, line) = self.lines.get(line.checked_sub(1)?)?;
+        Some((self.files[file].as_path(), line))
+    }
+
+    // Rewrites the `0(12)` (NVIDIA) and `0:12` (Mesa, AMD, Intel) locations of
+    // a compile log so they point to the original file and line.
+    pub fn translate_log(&self, log: &str) -> String {
+        let bytes = log.as_bytes();
+        let mut out = String::with_capacity(log.len());
+        let mut last = 0;
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let boundary = i == 0 || !bytes[i - 1].is_ascii_alphanumeric();
+            if boundary && bytes[i] == b'0' && i + 2 < bytes.len() {
+                let open = bytes[i + 1];
+                let digits = bytes[i + 2..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_digit())
+                    .count();
+                let end = i + 2 + digits;
+                let closed = match open {
+                    b'(' => end < bytes.len() && bytes[end] == b')',
+                    b':' => true,
+                    _ => false,
+                };
+
+                if digits > 0 && closed {
+                    let line: usize = log[i + 2..end].parse().unwrap_or(0);
+                    if let Some((file, line)) = self.resolve(line) {
+                        out.push_str(&log[last..i]);
+                        out.push_str(&format!("{}:{}", file.display(), line));
+                        last = if open == b'(' { end + 1 } else { end };
+                        i = last;
+                        continue;
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        out.push_str(&log[last..]);
+        out
+    }
+}
+
+impl Preprocessor {
+    pub fn with_include_root(mut self, root: &Path) -> Preprocessor {
+        self.include_roots.push(root.to_path_buf());
+        self
+    }
+
+    // Defining a name again replaces its value.
+    pub fn with_define(mut self, name: &str, value: &str) -> Preprocessor {
+        self.defines.retain(|(defined, _)| defined != name);
+        self.defines.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    // Code wrapped around the shader, e.g. to declare the uniforms and the
+    // `main` of a shader that only defines some entry point. The prelude goes
+    // after the `#version` of the shader and its defines, or first when the
+    // shader has none, so it then has to hold the `#version`.
+    pub fn with_prelude(mut self, prelude: &str) -> Preprocessor {
+        self.prelude.push_str(prelude);
+        self
+    }
+
+    pub fn with_epilogue(mut self, epilogue: &str) -> Preprocessor {
+        self.epilogue.push_str(epilogue);
+        self
+    }
+
+    // Generated lines are mapped to line 0 of the shader.
+    fn inject_lines(&self, output: &mut PreprocessedSource, lines: &str) {
+        for line in lines.lines() {
+            output.source.push_str(line);
+            output.source.push('\n');
+            output.source_map.push(0, 0);
+        }
+    }
+
+    // The defines go right after the `#version` directive, which must come
+    // before anything else, or at the very top when the shader has none.
+    fn inject_defines(&self, output: &mut PreprocessedSource, file: usize, line: usize) {
+        for (name, value) in &self.defines {
+            output
+                .source
+                .push_str(&format!("#define {} {}\n", name, value));
+            output.source_map.push(file, line);
+        }
+    }
+
+    fn resolve_include(&self, from: &Path, name: &str) -> Option<PathBuf> {
+        let local = from.parent().map(|dir| dir.join(name));
+        local
+            .into_iter()
+            .chain(self.include_roots.iter().map(|root| root.join(name)))
+            .find(|candidate| candidate.is_file())
+    }
+
+    pub fn process(&self, path: &Path) -> Result<PreprocessedSource, ShaderError> {
+        let mut output = PreprocessedSource {
+            source: String::new(),
+            source_map: SourceMap::default(),
+        };
+        let mut stack = Vec::new();
+        let mut included = HashSet::new();
+
+        self.process_file(path, &mut stack, &mut included, &mut output)?;
+        self.inject_lines(&mut output, &self.epilogue);
+
+        Ok(output)
+    }
+
+    fn process_file(
+        &self,
+        path: &Path,
+        stack: &mut Vec<PathBuf>,
+        included: &mut HashSet<PathBuf>,
+        output: &mut PreprocessedSource,
+    ) -> Result<(), ShaderError> {
+        let src = utils::load_file(path).map_err(|error| ShaderError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        stack.push(canonical.clone());
+        included.insert(canonical);
+
+        let file = output.source_map.files.len();
+        output.source_map.files.push(path.to_path_buf());
+
+        let version_line = if file == 0 {
+            src.lines()
+                .position(|line| line.trim_start().starts_with("#version"))
+        } else {
+            None
+        };
+        if file == 0 && version_line.is_none() {
+            self.inject_lines(output, &self.prelude);
+            self.inject_defines(output, file, 1);
+        }
+
+        let mut in_comment = false;
+        for (number, line) in src.lines().enumerate() {
+            let commented = in_comment;
+            in_comment = ends_in_comment(line, in_comment);
+            if !commented && is_pragma_once(line) {
+                continue;
+            }
+
+            let include = if commented { None } else { parse_include(line) };
+            let name = match include {
+                Some(name) => name,
+                None => {
+                    output.source.push_str(line);
+                    output.source.push('\n');
+                    output.source_map.push(file, number + 1);
+                    if version_line == Some(number) {
+                        self.inject_defines(output, file, number + 1);
+                        self.inject_lines(output, &self.prelude);
+                    }
+                    continue;
+                }
+            };
+
+            let resolved =
+                self.resolve_include(path, name)
+                    .ok_or_else(|| ShaderError::Include {
+                        path: path.to_path_buf(),
+                        line: number + 1,
+                        include: name.to_string(),
+                    })?;
+            let canonical = resolved.canonicalize().unwrap_or_else(|_| resolved.clone());
+
+            if stack.contains(&canonical) {
+                let mut chain = stack.clone();
+                chain.push(canonical);
+                return Err(ShaderError::IncludeCycle(chain));
+            }
+
+            if !included.contains(&canonical) {
+                self.process_file(&canonical, stack, included, output)?;
+            }
+        }
+
+        stack.pop();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // Fresh directory holding `files`, removed by the caller.
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("peglrs-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (name, source) in files {
+            fs::write(dir.join(name), source).unwrap();
+        }
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn diamond_include_is_emitted_once() {
+        let dir = write_files(
+            "diamond",
+            &[
+                (
+                    "main.glsl",
+                    "#include \"a.glsl\"\n#include \"b.glsl\"\nvoid main() {}\n",
+                ),
+                ("a.glsl", "#include \"common.glsl\"\nfloat a;\n"),
+                ("b.glsl", "#include \"common.glsl\"\nfloat b;\n"),
+                ("common.glsl", "#pragma once\nfloat common;\n"),
+            ],
+        );
+
+        let output = Preprocessor::default()
+            .process(&dir.join("main.glsl"))
+            .unwrap();
+        assert_eq!(
+            output.source,
+            "float common;\nfloat a;\nfloat b;\nvoid main() {}\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn include_cycle_is_an_error() {
+        let dir = write_files(
+            "cycle",
+            &[
+                ("a.glsl", "#include \"b.glsl\"\n"),
+                ("b.glsl", "#include \"a.glsl\"\n"),
+            ],
+        );
+
+        match Preprocessor::default().process(&dir.join("a.glsl")) {
+            Err(ShaderError::IncludeCycle(chain)) => assert_eq!(
+                chain,
+                vec![dir.join("a.glsl"), dir.join("b.glsl"), dir.join("a.glsl")]
+            ),
+            other => panic!("expected an include cycle, got {:?}", other),
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn log_lines_map_to_the_original_file() {
+        let dir = write_files(
+            "log",
+            &[
+                (
+                    "main.glsl",
+                    "#version 450\n#include \"lib.glsl\"\nvoid main() {}\n",
+                ),
+                ("lib.glsl", "float f;\nfloat g\n"),
+            ],
+        );
+
+        let output = Preprocessor::default()
+            .with_define("QUALITY", "2")
+            .process(&dir.join("main.glsl"))
+            .unwrap();
+        // #version, #define, f, g, main.
+        let lib = dir.join("lib.glsl");
+        let main = dir.join("main.glsl");
+        assert_eq!(
+            output
+                .source_map
+                .translate_log("0(4) : error C0000: syntax error"),
+            format!("{}:2 : error C0000: syntax error", lib.display())
+        );
+        assert_eq!(
+            output
+                .source_map
+                .translate_log("ERROR: 0:5: 'main' : redefinition"),
+            format!("ERROR: {}:3: 'main' : redefinition", main.display())
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prelude_goes_after_the_version() {
+        let dir = write_files(
+            "prelude",
+            &[
+                (
+                    "versioned.frag",
+                    "// header\n#version 450\nvoid main() {}\n",
+                ),
+                ("bare.frag", "void main() {}\n"),
+            ],
+        );
+        let versioned = Preprocessor::default()
+            .with_define("A", "1")
+            .with_prelude("uniform float time;\n")
+            .process(&dir.join("versioned.frag"))
+            .unwrap();
+        assert_eq!(
+            versioned.source,
+            "// header\n#version 450\n#define A 1\nuniform float time;\nvoid main() {}\n"
+        );
+        // main() is still mapped to its own line.
+        assert_eq!(
+            versioned.source_map.translate_log("0(5) : error"),
+            format!("{}:3 : error", dir.join("versioned.frag").display())
+        );
+
+        let bare = Preprocessor::default()
+            .with_define("A", "1")
+            .with_prelude("#version 330\nuniform float time;\n")
+            .process(&dir.join("bare.frag"))
+            .unwrap();
+        assert_eq!(
+            bare.source,
+            "#version 330\nuniform float time;\n#define A 1\nvoid main() {}\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+
+use gl;
+
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UniformType {
+    FLOAT,
+    VEC2,
+    VEC3,
+    VEC4,
+    INT,
+    IVEC2,
+    IVEC3,
+    IVEC4,
+    UINT,
+    UVEC2,
+    UVEC3,
+    UVEC4,
+    BOOL,
+    BVEC2,
+    BVEC3,
+    BVEC4,
+    MAT2,
+    MAT3,
+    MAT4,
+    MAT2x3,
+    MAT2x4,
+    MAT3x2,
+    MAT3x4,
+    MAT4x2,
+    MAT4x3,
+    SAMPLER(u32),
+    IMAGE(u32),
+    OTHER(u32),
+}
+
+#[derive(Debug, Clone)]
+pub struct ActiveUniform {
+    pub name: String,
+    pub uniform_type: UniformType,
+    pub size: i32,
+    pub location: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ActiveAttribute {
+    pub name: String,
+    pub attribute_type: UniformType,
+    pub size: i32,
+    pub location: i32,
+}
+
+impl UniformType {
+    // Components of the type, or of one column for matrices.
+    pub fn components(self) -> i32 {
+        match self {
+            UniformType::VEC2 | UniformType::IVEC2 | UniformType::UVEC2 | UniformType::BVEC2 => 2,
+            UniformType::VEC3 | UniformType::IVEC3 | UniformType::UVEC3 | UniformType::BVEC3 => 3,
+            UniformType::VEC4 | UniformType::IVEC4 | UniformType::UVEC4 | UniformType::BVEC4 => 4,
+            UniformType::MAT2 | UniformType::MAT3x2 | UniformType::MAT4x2 => 2,
+            UniformType::MAT3 | UniformType::MAT2x3 | UniformType::MAT4x3 => 3,
+            UniformType::MAT4 | UniformType::MAT2x4 | UniformType::MAT3x4 => 4,
+            _ => 1,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(
+            self,
+            UniformType::FLOAT
+                | UniformType::VEC2
+                | UniformType::VEC3
+                | UniformType::VEC4
+                | UniformType::MAT2
+                | UniformType::MAT3
+                | UniformType::MAT4
+                | UniformType::MAT2x3
+                | UniformType::MAT2x4
+                | UniformType::MAT3x2
+                | UniformType::MAT3x4
+                | UniformType::MAT4x2
+                | UniformType::MAT4x3
+        )
+    }
+
+    // Samplers and images of any dimension are considered the same kind, since
+    // they are all set through their texture or image unit.
+    pub fn same_kind(self, other: UniformType) -> bool {
+        match (self, other) {
+            (UniformType::SAMPLER(_), UniformType::SAMPLER(_)) => true,
+            (UniformType::IMAGE(_), UniformType::IMAGE(_)) => true,
+            _ => self == other,
+        }
+    }
+
+    pub fn from_gl(gl_type: u32) -> UniformType {
+        match gl_type {
+            gl::FLOAT => UniformType::FLOAT,
+            gl::FLOAT_VEC2 => UniformType::VEC2,
+            gl::FLOAT_VEC3 => UniformType::VEC3,
+            gl::FLOAT_VEC4 => UniformType::VEC4,
+            gl::INT => UniformType::INT,
+            gl::INT_VEC2 => UniformType::IVEC2,
+            gl::INT_VEC3 => UniformType::IVEC3,
+            gl::INT_VEC4 => UniformType::IVEC4,
+            gl::UNSIGNED_INT => UniformType::UINT,
+            gl::UNSIGNED_INT_VEC2 => UniformType::UVEC2,
+            gl::UNSIGNED_INT_VEC3 => UniformType::UVEC3,
+            gl::UNSIGNED_INT_VEC4 => UniformType::UVEC4,
+            gl::BOOL => UniformType::BOOL,
+            gl::BOOL_VEC2 => UniformType::BVEC2,
+            gl::BOOL_VEC3 => UniformType::BVEC3,
+            gl::BOOL_VEC4 => UniformType::BVEC4,
+            gl::FLOAT_MAT2 => UniformType::MAT2,
+            gl::FLOAT_MAT3 => UniformType::MAT3,
+            gl::FLOAT_MAT4 => UniformType::MAT4,
+            gl::FLOAT_MAT2x3 => UniformType::MAT2x3,
+            gl::FLOAT_MAT2x4 => UniformType::MAT2x4,
+            gl::FLOAT_MAT3x2 => UniformType::MAT3x2,
+            gl::FLOAT_MAT3x4 => UniformType::MAT3x4,
+            gl::FLOAT_MAT4x2 => UniformType::MAT4x2,
+            gl::FLOAT_MAT4x3 => UniformType::MAT4x3,
+            gl::SAMPLER_1D
+            | gl::SAMPLER_2D
+            | gl::SAMPLER_3D
+            | gl::SAMPLER_CUBE
+            | gl::SAMPLER_1D_SHADOW
+            | gl::SAMPLER_2D_SHADOW
+            | gl::SAMPLER_1D_ARRAY
+            | gl::SAMPLER_2D_ARRAY
+            | gl::SAMPLER_CUBE_SHADOW
+            | gl::SAMPLER_2D_ARRAY_SHADOW
+            | gl::SAMPLER_2D_MULTISAMPLE
+            | gl::SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::SAMPLER_BUFFER
+            | gl::SAMPLER_2D_RECT
+            | gl::INT_SAMPLER_2D
+            | gl::INT_SAMPLER_3D
+            | gl::INT_SAMPLER_CUBE
+            | gl::INT_SAMPLER_2D_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_2D
+            | gl::UNSIGNED_INT_SAMPLER_3D
+            | gl::UNSIGNED_INT_SAMPLER_CUBE
+            | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY => UniformType::SAMPLER(gl_type),
+            gl::IMAGE_1D
+            | gl::IMAGE_2D
+            | gl::IMAGE_3D
+            | gl::IMAGE_CUBE
+            | gl::IMAGE_2D_ARRAY
+            | gl::IMAGE_BUFFER
+            | gl::INT_IMAGE_2D
+            | gl::INT_IMAGE_3D
+            | gl::UNSIGNED_INT_IMAGE_2D
+            | gl::UNSIGNED_INT_IMAGE_3D => UniformType::IMAGE(gl_type),
+            _ => UniformType::OTHER(gl_type),
+        }
+    }
+}
+
+// Name and `props` values of every active resource of `interface`. Queried by
+// resource index, so it also works for SPIR-V programs whose names were
+// stripped. Needs GL 4.3 or ARB_program_interface_query.
+fn resources(program: u32, interface: u32, props: &[u32]) -> Vec<(String, Vec<i32>)> {
+    let mut resources = Vec::new();
+
+    unsafe {
+        let mut count: i32 = 0;
+        gl::GetProgramInterfaceiv(program, interface, gl::ACTIVE_RESOURCES, &mut count);
+
+        for index in 0..count.max(0) as u32 {
+            let mut values = vec![0; props.len()];
+            gl::GetProgramResourceiv(
+                program,
+                interface,
+                index,
+                props.len() as i32,
+                props.as_ptr(),
+                values.len() as i32,
+                std::ptr::null_mut(),
+                values.as_mut_ptr(),
+            );
+
+            let mut name_len: i32 = 0;
+            gl::GetProgramResourceiv(
+                program,
+                interface,
+                index,
+                1,
+                &gl::NAME_LENGTH,
+                1,
+                std::ptr::null_mut(),
+                &mut name_len,
+            );
+            let mut name: Vec<u8> = vec![0; name_len.max(1) as usize];
+            let mut written: i32 = 0;
+            gl::GetProgramResourceName(
+                program,
+                interface,
+                index,
+                name.len() as i32,
+                &mut written,
+                name.as_mut_ptr() as *mut i8,
+            );
+            name.truncate(written.max(0) as usize);
+
+            resources.push((String::from_utf8_lossy(&name).into_owned(), values));
+        }
+    }
+
+    resources
+}
+
+// Key of resources without a name, e.g. in stripped SPIR-V modules: their
+// location in decimal, which can't clash with a GLSL identifier.
+fn resource_key(name: &str, location: i32) -> String {
+    if name.is_empty() {
+        location.to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+// Enumerates the active uniforms of a linked program. Arrays are stored under
+// their base name (`lights` rather than `lights[0]`) and struct members under
+// their full path (`light.position`). Uniforms living in a block have no
+// location and are left out. Uniforms without a name are stored under their
+// location, e.g. `"3"`.
+pub fn reflect_uniforms(program: u32) -> HashMap<String, ActiveUniform> {
+    let props = [gl::TYPE, gl::ARRAY_SIZE, gl::LOCATION];
+    let mut uniforms = HashMap::new();
+
+    for (name, values) in resources(program, gl::UNIFORM, &props) {
+        let (gl_type, size, location) = (values[0] as u32, values[1], values[2]);
+        if location < 0 {
+            continue;
+        }
+
+        // Only the last `[0]`, `a[0][0]` is the array `a[0]`.
+        let name = name.strip_suffix("[0]").unwrap_or(&name);
+        let name = resource_key(name, location);
+        uniforms.insert(
+            name.clone(),
+            ActiveUniform {
+                name,
+                uniform_type: UniformType::from_gl(gl_type),
+                size: size.max(1),
+                location,
+            },
+        );
+    }
+
+    uniforms
+}
+
+// Enumerates the active vertex inputs of a linked program. Built-in inputs
+// such as `gl_VertexID` have no location and are left out, inputs without a
+// name are stored under their location.
+pub fn reflect_attributes(program: u32) -> HashMap<String, ActiveAttribute> {
+    let props = [
+        gl::TYPE,
+        gl::ARRAY_SIZE,
+        gl::LOCATION,
+        gl::REFERENCED_BY_VERTEX_SHADER,
+    ];
+    let mut attributes = HashMap::new();
+
+    for (name, values) in resources(program, gl::PROGRAM_INPUT, &props) {
+        let (gl_type, size, location) = (values[0] as u32, values[1], values[2]);
+        // The inputs of a separable program without vertex stage belong to
+        // its first stage.
+        if location < 0 || values[3] == 0 {
+            continue;
+        }
+
+        let name = resource_key(&name, location);
+        attributes.insert(
+            name.clone(),
+            ActiveAttribute {
+                name,
+                attribute_type: UniformType::from_gl(gl_type),
+                size: size.max(1),
+                location,
+            },
+        );
+    }
+
+    attributes
+}
@@ -1,4 +1,3 @@
-use crate::utils;
 use gl;
 use std::path::Path;
 
@@ -6,47 +5,100 @@ use std::ffi::CString;
 use std::ptr;
 use std::rc::Rc;
 
-use cgmath::{Array, Matrix, Matrix4, Vector2, Vector3, Vector4};
+use cgmath::{Array, Matrix, Matrix2, Matrix3, Matrix4, Vector2, Vector3, Vector4};
 
+use super::binary_cache;
+use super::manifest::UniformValue;
+use super::preprocessor::Preprocessor;
+use super::reflection::{reflect_attributes, reflect_uniforms, UniformType};
+use super::spirv::{self, Specialization};
+use super::std140::Std140;
+use super::uniform_buffer::UniformBuffer;
+use super::uniform_cache::{UniformCache, UniformStats};
 use super::*;
 
-pub fn parse_uniforms(src: &str) -> Vec<String> {
-    let mut uniforms: Vec<String> = Vec::new();
-    for line in src.lines() {
-        if line.starts_with("uniform ") {
-            let attrb: Vec<&str> = line.split(' ').collect();
-            if attrb.len() < 3 {
-                continue;
-            }
-
-            let uniform_name = String::from(attrb[2]).trim_end_matches(';').to_string();
-            uniforms.push(uniform_name);
-        }
+pub(super) fn shader_info_log(addr: u32) -> String {
+    unsafe {
+        let mut log_len: i32 = 0;
+        gl::GetShaderiv(addr, gl::INFO_LOG_LENGTH, &mut log_len);
+        let mut log: Vec<u8> = Vec::with_capacity(log_len as usize);
+        gl::GetShaderInfoLog(addr, log_len, ptr::null_mut(), log.as_mut_ptr() as *mut i8);
+        log.set_len(log_len as usize);
+        String::from_utf8_lossy(&log[..])
+            .trim_end_matches('\0')
+            .to_string()
     }
+}
 
-    uniforms
+fn program_info_log(addr: u32) -> String {
+    unsafe {
+        let mut log_len: i32 = 0;
+        gl::GetProgramiv(addr, gl::INFO_LOG_LENGTH, &mut log_len);
+        let mut log: Vec<u8> = Vec::with_capacity(log_len as usize);
+        gl::GetProgramInfoLog(addr, log_len, ptr::null_mut(), log.as_mut_ptr() as *mut i8);
+        log.set_len(log_len as usize);
+        String::from_utf8_lossy(&log[..])
+            .trim_end_matches('\0')
+            .to_string()
+    }
 }
 
 impl Shader {
-    pub fn load_shader(path: &Path) -> Option<Shader> {
-        #[cfg(feature = "debug")]
-        println!("[NFO] Loading shader {}", path.display());
+    pub fn load_shader(path: &Path) -> Result<Shader, ShaderError> {
+        Shader::load_shader_with_preprocessor(path, &Preprocessor::default())
+    }
 
-        let src = utils::load_file(path).and_then(|shd_src| CString::new(shd_src.as_bytes()).ok());
-        if src.is_none() {
-            #[cfg(feature = "debug")]
-            eprintln!("[ERR] Couldn't load source for shader {}", path.display());
+    pub fn load_shader_with_defines(
+        path: &Path,
+        defines: &[(&str, &str)],
+    ) -> Result<Shader, ShaderError> {
+        let preprocessor = defines
+            .iter()
+            .fold(Preprocessor::default(), |preprocessor, (name, value)| {
+                preprocessor.with_define(name, value)
+            });
+        Shader::load_shader_with_preprocessor(path, &preprocessor)
+    }
+
+    pub fn load_shader_with_preprocessor(
+        path: &Path,
+        preprocessor: &Preprocessor,
+    ) -> Result<Shader, ShaderError> {
+        Shader::compile(path, preprocessor, None)
+    }
 
-            return None;
+    // Compiles the shader as `shader_type` whatever its extension says.
+    pub fn load_shader_as(
+        path: &Path,
+        shader_type: ShaderType,
+        preprocessor: &Preprocessor,
+    ) -> Result<Shader, ShaderError> {
+        Shader::compile(path, preprocessor, Some(shader_type))
+    }
+
+    fn compile(
+        path: &Path,
+        preprocessor: &Preprocessor,
+        explicit_type: Option<ShaderType>,
+    ) -> Result<Shader, ShaderError> {
+        if spirv::is_spirv_path(path) {
+            return Shader::load_spirv(path, explicit_type, &Specialization::default());
         }
-        let src = src.unwrap();
 
-        let shader_type = get_shader_type(path);
+        #[cfg(feature = "debug")]
+        println!("[NFO] Loading shader {}", path.display());
+
+        let preprocessed = preprocessor.process(path)?;
+        let src = CString::new(preprocessed.source.as_bytes())?;
+
+        let shader_type = explicit_type
+            .or_else(|| get_shader_type(path))
+            .or_else(|| get_pragma_shader_type(&preprocessed.source));
         if shader_type.is_none() {
             #[cfg(feature = "debug")]
             eprintln!("[ERR] Couldn't detect shader type for {}", path.display());
 
-            return None;
+            return Err(ShaderError::UnknownExtension(path.to_path_buf()));
         }
 
         let gl_type = get_gl_shader_type(&shader_type);
@@ -58,36 +110,56 @@ impl Shader {
             let mut status: i32 = 0;
             gl::GetShaderiv(addr, gl::COMPILE_STATUS, &mut status);
             if status == i32::from(gl::FALSE) {
+                let log = preprocessed
+                    .source_map
+                    .translate_log(&shader_info_log(addr));
+                gl::DeleteShader(addr);
+
                 #[cfg(feature = "debug")]
-                {
-                    let mut log_len: i32 = 0;
-                    gl::GetShaderiv(addr, gl::INFO_LOG_LENGTH, &mut log_len);
-                    let mut log: Vec<u8> = Vec::with_capacity(log_len as usize);
-                    gl::GetShaderInfoLog(
-                        addr,
-                        log_len,
-                        ptr::null_mut(),
-                        log.as_mut_ptr() as *mut i8,
-                    );
-                    log.set_len(log_len as usize);
-                    eprintln!(
-                        "[ERR] Couldn't compile shader {}, log:\n{}",
-                        path.display(),
-                        String::from_utf8_lossy(&log[..])
-                    );
-                }
+                eprintln!(
+                    "[ERR] Couldn't compile shader {}, log:\n{}",
+                    path.display(),
+                    log
+                );
 
-                return None;
+                return Err(ShaderError::Compile {
+                    path: path.to_path_buf(),
+                    log,
+                });
             }
 
-            Some(Shader {
+            Ok(Shader {
                 addr,
-                path: String::from(path.to_str().unwrap()),
-                uniforms: parse_uniforms(&src.to_string_lossy()),
+                path: path.to_string_lossy().into_owned(),
+                source: preprocessed.source,
+                includes: preprocessed.source_map.files[1..]
+                    .iter()
+                    .map(|include| include.to_string_lossy().into_owned())
+                    .collect(),
                 shader_type: shader_type.unwrap(),
+                explicit_type: explicit_type.is_some(),
+                preprocessor: preprocessor.clone(),
+                spirv: None,
             })
         }
     }
+
+    pub fn reload(&self) -> Result<Shader, ShaderError> {
+        let explicit_type = if self.explicit_type {
+            Some(self.shader_type)
+        } else {
+            None
+        };
+        let path = Path::new(&self.path);
+        match &self.spirv {
+            Some(module) => Shader::load_spirv(path, explicit_type, &module.specialization),
+            None => Shader::compile(path, &self.preprocessor, explicit_type),
+        }
+    }
+
+    pub fn depends_on(&self, path: &Path) -> bool {
+        Path::new(&self.path) == path || self.includes.iter().any(|inc| Path::new(inc) == path)
+    }
 }
 
 impl Program {
@@ -97,92 +169,406 @@ impl Program {
         }
     }
 
+    pub fn stage_bits(&self) -> u32 {
+        self.shaders.iter().fold(0, |bits, shader| {
+            bits | get_gl_shader_stage_bit(shader.shader_type)
+        })
+    }
+
     pub fn unbind() {
         unsafe {
             gl::UseProgram(0);
         }
     }
 
-    pub fn set_float(&self, name: &str, value: f32) {
-        unsafe {
-            gl::Uniform1f(self.uniforms_location[name], value);
+    fn uniform_location(
+        &self,
+        name: &str,
+        expected: UniformType,
+        count: usize,
+    ) -> Result<Option<i32>, UniformError> {
+        let uniform = match self.uniforms.get(name) {
+            Some(uniform) => uniform,
+            None if self.skip_missing_uniforms => return Ok(None),
+            None => return Err(UniformError::Missing(name.to_string())),
+        };
+
+        if !uniform.uniform_type.same_kind(expected) {
+            return Err(UniformError::TypeMismatch {
+                name: name.to_string(),
+                expected,
+                found: uniform.uniform_type,
+            });
+        }
+
+        if count > uniform.size as usize {
+            return Err(UniformError::ArrayOverflow {
+                name: name.to_string(),
+                size: uniform.size as usize,
+                count,
+            });
+        }
+
+        Ok(Some(uniform.location))
+    }
+
+    pub fn set_float(&self, name: &str, value: f32) -> Result<(), UniformError> {
+        if let Some(location) = self.uniform_location(name, UniformType::FLOAT, 1)? {
+            self.uniform_cache.upload(location, &value, || unsafe {
+                gl::Uniform1f(location, value);
+            });
         }
+        Ok(())
     }
 
-    pub fn set_vec2(&self, name: &str, value: &Vector2<f32>) {
-        unsafe {
-            gl::Uniform2fv(self.uniforms_location[name], 1, value.as_ptr());
+    pub fn set_int(&self, name: &str, value: i32) -> Result<(), UniformError> {
+        if let Some(location) = self.uniform_location(name, UniformType::INT, 1)? {
+            self.uniform_cache.upload(location, &value, || unsafe {
+                gl::Uniform1i(location, value);
+            });
         }
+        Ok(())
     }
 
-    pub fn set_vec3(&self, name: &str, value: &Vector3<f32>) {
-        unsafe {
-            gl::Uniform3fv(self.uniforms_location[name], 1, value.as_ptr());
+    pub fn set_uint(&self, name: &str, value: u32) -> Result<(), UniformError> {
+        if let Some(location) = self.uniform_location(name, UniformType::UINT, 1)? {
+            self.uniform_cache.upload(location, &value, || unsafe {
+                gl::Uniform1ui(location, value);
+            });
         }
+        Ok(())
     }
 
-    pub fn set_vec4(&self, name: &str, value: &Vector4<f32>) {
-        unsafe {
-            gl::Uniform4fv(self.uniforms_location[name], 1, value.as_ptr());
+    pub fn set_bool(&self, name: &str, value: bool) -> Result<(), UniformError> {
+        if let Some(location) = self.uniform_location(name, UniformType::BOOL, 1)? {
+            self.uniform_cache.upload(location, &value, || unsafe {
+                gl::Uniform1i(location, value as i32);
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_sampler(&self, name: &str, unit: u32) -> Result<(), UniformError> {
+        let expected = UniformType::SAMPLER(gl::SAMPLER_2D);
+        if let Some(location) = self.uniform_location(name, expected, 1)? {
+            self.uniform_cache.upload(location, &unit, || unsafe {
+                gl::Uniform1i(location, unit as i32);
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_vec2(&self, name: &str, value: &Vector2<f32>) -> Result<(), UniformError> {
+        if let Some(location) = self.uniform_location(name, UniformType::VEC2, 1)? {
+            self.uniform_cache.upload(location, value, || unsafe {
+                gl::Uniform2fv(location, 1, value.as_ptr());
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_vec3(&self, name: &str, value: &Vector3<f32>) -> Result<(), UniformError> {
+        if let Some(location) = self.uniform_location(name, UniformType::VEC3, 1)? {
+            self.uniform_cache.upload(location, value, || unsafe {
+                gl::Uniform3fv(location, 1, value.as_ptr());
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_vec4(&self, name: &str, value: &Vector4<f32>) -> Result<(), UniformError> {
+        if let Some(location) = self.uniform_location(name, UniformType::VEC4, 1)? {
+            self.uniform_cache.upload(location, value, || unsafe {
+                gl::Uniform4fv(location, 1, value.as_ptr());
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_ivec2(&self, name: &str, value: &Vector2<i32>) -> Result<(), UniformError> {
+        if let Some(location) = self.uniform_location(name, UniformType::IVEC2, 1)? {
+            self.uniform_cache.upload(location, value, || unsafe {
+                gl::Uniform2iv(location, 1, value.as_ptr());
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_ivec3(&self, name: &str, value: &Vector3<i32>) -> Result<(), UniformError> {
+        if let Some(location) = self.uniform_location(name, UniformType::IVEC3, 1)? {
+            self.uniform_cache.upload(location, value, || unsafe {
+                gl::Uniform3iv(location, 1, value.as_ptr());
+            });
         }
+        Ok(())
     }
 
-    pub fn set_mat4(&self, name: &str, value: &Matrix4<f32>) {
+    pub fn set_ivec4(&self, name: &str, value: &Vector4<i32>) -> Result<(), UniformError> {
+        if let Some(location) = self.uniform_location(name, UniformType::IVEC4, 1)? {
+            self.uniform_cache.upload(location, value, || unsafe {
+                gl::Uniform4iv(location, 1, value.as_ptr());
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_mat2(&self, name: &str, value: &Matrix2<f32>) -> Result<(), UniformError> {
+        if let Some(location) = self.uniform_location(name, UniformType::MAT2, 1)? {
+            self.uniform_cache.upload(location, value, || unsafe {
+                gl::UniformMatrix2fv(location, 1, gl::FALSE, value.as_ptr());
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_mat3(&self, name: &str, value: &Matrix3<f32>) -> Result<(), UniformError> {
+        if let Some(location) = self.uniform_location(name, UniformType::MAT3, 1)? {
+            self.uniform_cache.upload(location, value, || unsafe {
+                gl::UniformMatrix3fv(location, 1, gl::FALSE, value.as_ptr());
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_mat4(&self, name: &str, value: &Matrix4<f32>) -> Result<(), UniformError> {
+        if let Some(location) = self.uniform_location(name, UniformType::MAT4, 1)? {
+            self.uniform_cache.upload(location, value, || unsafe {
+                gl::UniformMatrix4fv(location, 1, gl::FALSE, value.as_ptr());
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_float_array(&self, name: &str, values: &[f32]) -> Result<(), UniformError> {
+        let count = values.len();
+        if let Some(location) = self.uniform_location(name, UniformType::FLOAT, count)? {
+            self.uniform_cache.upload(location, values, || unsafe {
+                gl::Uniform1fv(location, count as i32, values.as_ptr());
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_int_array(&self, name: &str, values: &[i32]) -> Result<(), UniformError> {
+        let count = values.len();
+        if let Some(location) = self.uniform_location(name, UniformType::INT, count)? {
+            self.uniform_cache.upload(location, values, || unsafe {
+                gl::Uniform1iv(location, count as i32, values.as_ptr());
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_vec2_array(&self, name: &str, values: &[Vector2<f32>]) -> Result<(), UniformError> {
+        let count = values.len();
+        if let Some(location) = self.uniform_location(name, UniformType::VEC2, count)? {
+            self.uniform_cache.upload(location, values, || unsafe {
+                gl::Uniform2fv(location, count as i32, values.as_ptr() as *const f32);
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_vec3_array(&self, name: &str, values: &[Vector3<f32>]) -> Result<(), UniformError> {
+        let count = values.len();
+        if let Some(location) = self.uniform_location(name, UniformType::VEC3, count)? {
+            self.uniform_cache.upload(location, values, || unsafe {
+                gl::Uniform3fv(location, count as i32, values.as_ptr() as *const f32);
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_vec4_array(&self, name: &str, values: &[Vector4<f32>]) -> Result<(), UniformError> {
+        let count = values.len();
+        if let Some(location) = self.uniform_location(name, UniformType::VEC4, count)? {
+            self.uniform_cache.upload(location, values, || unsafe {
+                gl::Uniform4fv(location, count as i32, values.as_ptr() as *const f32);
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_mat4_array(&self, name: &str, values: &[Matrix4<f32>]) -> Result<(), UniformError> {
+        let count = values.len();
+        if let Some(location) = self.uniform_location(name, UniformType::MAT4, count)? {
+            self.uniform_cache.upload(location, values, || unsafe {
+                gl::UniformMatrix4fv(
+                    location,
+                    count as i32,
+                    gl::FALSE,
+                    values.as_ptr() as *const f32,
+                );
+            });
+        }
+        Ok(())
+    }
+
+    pub fn set_uniform(&self, name: &str, value: &UniformValue) -> Result<(), UniformError> {
+        match value {
+            UniformValue::Float(value) => self.set_float(name, *value),
+            UniformValue::Int(value) => self.set_int(name, *value),
+            UniformValue::UInt(value) => self.set_uint(name, *value),
+            UniformValue::Bool(value) => self.set_bool(name, *value),
+            UniformValue::Vec2(value) => self.set_vec2(name, value),
+            UniformValue::Vec3(value) => self.set_vec3(name, value),
+            UniformValue::Vec4(value) => self.set_vec4(name, value),
+            UniformValue::Sampler(unit) => self.set_sampler(name, *unit),
+        }
+    }
+
+    pub fn apply_default_uniforms(&self) -> Result<(), UniformError> {
+        if self.default_uniforms.is_empty() {
+            return Ok(());
+        }
+
+        self.bind();
+        for (name, value) in &self.default_uniforms {
+            self.set_uniform(name, value)?;
+        }
+        Program::unbind();
+        Ok(())
+    }
+
+    // Links `shaders` into a new program keeping the settings and default
+    // uniforms of this one, e.g. after one of its shaders was reloaded.
+    pub fn relink(&self, shaders: &[Rc<Shader>]) -> Result<Program, ShaderError> {
+        let mut program = Program::build(shaders, self.separable)?;
+        program.skip_missing_uniforms = self.skip_missing_uniforms;
+        program.manifest = self.manifest.clone();
+        program.default_uniforms = self.default_uniforms.clone();
+        program.apply_default_uniforms()?;
+        Ok(program)
+    }
+
+    // Uploads done and avoided by the shadow copy of the uniform values.
+    pub fn uniform_stats(&self) -> UniformStats {
+        self.uniform_cache.stats()
+    }
+
+    pub fn reset_uniform_stats(&self) {
+        self.uniform_cache.reset_stats();
+    }
+
+    pub fn has_uniform_block(&self, name: &str) -> bool {
+        match CString::new(name.as_bytes()) {
+            Ok(name_cstr) => unsafe {
+                gl::GetUniformBlockIndex(self.addr, name_cstr.as_ptr()) != gl::INVALID_INDEX
+            },
+            Err(_) => false,
+        }
+    }
+
+    pub fn bind_uniform_block(&self, name: &str, binding: u32) -> Result<(), UniformError> {
+        let name_cstr = match CString::new(name.as_bytes()) {
+            Ok(name_cstr) => name_cstr,
+            Err(_) => return Err(UniformError::Missing(name.to_string())),
+        };
+
         unsafe {
-            gl::UniformMatrix4fv(self.uniforms_location[name], 1, gl::FALSE, value.as_ptr());
+            let index = gl::GetUniformBlockIndex(self.addr, name_cstr.as_ptr());
+            if index == gl::INVALID_INDEX {
+                if self.skip_missing_uniforms {
+                    return Ok(());
+                }
+                return Err(UniformError::Missing(name.to_string()));
+            }
+            gl::UniformBlockBinding(self.addr, index, binding);
         }
+        Ok(())
+    }
+
+    pub fn bind_uniform_buffer<T: Std140>(
+        &self,
+        name: &str,
+        buffer: &UniformBuffer<T>,
+    ) -> Result<(), UniformError> {
+        self.bind_uniform_block(name, buffer.binding)
+    }
+
+    pub fn load_program(shaders: &[Rc<Shader>]) -> Result<Program, ShaderError> {
+        Program::build(shaders, false)
+    }
+
+    // Links a program with GL_PROGRAM_SEPARABLE, so its stages can be mixed
+    // with the stages of other separable programs in a `ProgramPipeline`.
+    pub fn load_separable(shaders: &[Rc<Shader>]) -> Result<Program, ShaderError> {
+        Program::build(shaders, true)
     }
 
-    pub fn load_program(shaders: &Vec<Rc<Shader>>) -> Option<Program> {
+    // A cached binary replaces the link, the stages are compiled either way.
+    fn build(shaders: &[Rc<Shader>], separable: bool) -> Result<Program, ShaderError> {
+        let cache = binary_cache::cache_dir().map(|dir| {
+            let key = binary_cache::cache_key(shaders, separable);
+            (dir, key)
+        });
+
+        let cached = cache
+            .as_ref()
+            .and_then(|(dir, key)| binary_cache::load(dir, key, separable));
+        let addr = match cached {
+            Some(addr) => addr,
+            None => {
+                let addr = Program::link(shaders, cache.is_some(), separable)?;
+                if let Some((dir, key)) = &cache {
+                    binary_cache::store(dir, key, addr);
+                }
+                addr
+            }
+        };
+
+        Ok(Program {
+            addr,
+            shaders: shaders.iter().map(Rc::clone).collect(),
+            uniforms: reflect_uniforms(addr),
+            attributes: reflect_attributes(addr),
+            separable,
+            skip_missing_uniforms: true,
+            uniform_cache: UniformCache::default(),
+            manifest: None,
+            default_uniforms: Vec::new(),
+        })
+    }
+
+    fn link(
+        shaders: &[Rc<Shader>],
+        retrievable: bool,
+        separable: bool,
+    ) -> Result<u32, ShaderError> {
         unsafe {
             let addr = gl::CreateProgram();
             for shader in shaders {
                 gl::AttachShader(addr, shader.addr);
             }
+            if separable {
+                gl::ProgramParameteri(addr, gl::PROGRAM_SEPARABLE, i32::from(gl::TRUE));
+            }
+            if retrievable {
+                gl::ProgramParameteri(
+                    addr,
+                    gl::PROGRAM_BINARY_RETRIEVABLE_HINT,
+                    i32::from(gl::TRUE),
+                );
+            }
             gl::LinkProgram(addr);
 
             let mut status: i32 = 0;
             gl::GetProgramiv(addr, gl::LINK_STATUS, &mut status);
             if status == i32::from(gl::FALSE) {
+                let log = program_info_log(addr);
+                gl::DeleteProgram(addr);
+
                 #[cfg(feature = "debug")]
-                {
-                    let mut log_length: i32 = 0;
-                    gl::GetProgramiv(addr, gl::INFO_LOG_LENGTH, &mut log_length);
-                    let mut log: Vec<u8> = Vec::with_capacity(log_length as usize);
-                    gl::GetProgramInfoLog(
-                        addr,
-                        log_length,
-                        ptr::null_mut(),
-                        log.as_mut_ptr() as *mut i8,
-                    );
-                    log.set_len(log_length as usize);
-                    eprintln!(
-                        "[ERR] Couldn't link program, log:\n{}",
-                        String::from_utf8_lossy(&log[..])
-                    );
-                }
+                eprintln!("[ERR] Couldn't link program, log:\n{}", log);
 
-                return None;
+                return Err(ShaderError::Link { log });
             }
 
-            let mut program = Program {
-                addr,
-                shaders: Vec::with_capacity(shaders.len()),
-                uniforms_location: HashMap::new(),
-            };
-
-            for shader in shaders.into_iter() {
+            for shader in shaders {
                 gl::DetachShader(addr, shader.addr);
-                program.shaders.push(Rc::clone(shader));
-
-                for uniform in &shader.uniforms {
-                    let uniform_cstr = CString::new(uniform.as_bytes()).unwrap();
-                    let location = gl::GetUniformLocation(addr, uniform_cstr.as_ptr());
-                    program.uniforms_location.insert(uniform.clone(), location);
-                }
             }
 
-            Some(program)
+            Ok(addr)
         }
     }
 }
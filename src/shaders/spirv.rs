@@ -0,0 +1,231 @@
+use std::cell::Cell;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::mem;
+use std::os::raw::{c_char, c_void};
+use std::path::Path;
+
+use gl;
+use gl_loader;
+
+use super::shader_loader::shader_info_log;
+use super::*;
+
+// Neither in the GL 4.5 bindings of the gl crate: glSpecializeShader is
+// GL 4.6, or glSpecializeShaderARB from GL_ARB_gl_spirv with the same enum.
+const SHADER_BINARY_FORMAT_SPIR_V: u32 = 0x9551;
+const SPIR_V_MAGIC: u32 = 0x0723_0203;
+
+type SpecializeShader = extern "system" fn(u32, *const c_char, u32, *const u32, *const u32);
+
+thread_local! {
+    static SPECIALIZE_SHADER: Cell<Option<Option<SpecializeShader>>> = const { Cell::new(None) };
+}
+
+fn has_extension(name: &str) -> bool {
+    unsafe {
+        let mut count: i32 = 0;
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+        (0..count as u32).any(|index| {
+            let extension = gl::GetStringi(gl::EXTENSIONS, index);
+            !extension.is_null()
+                && CStr::from_ptr(extension as *const c_char).to_bytes() == name.as_bytes()
+        })
+    }
+}
+
+// Looked up once, after checking the context supports SPIR-V since some
+// platforms hand out pointers for any name.
+fn specialize_shader() -> Option<SpecializeShader> {
+    SPECIALIZE_SHADER.with(|cached| {
+        if let Some(function) = cached.get() {
+            return function;
+        }
+
+        let (mut major, mut minor) = (0, 0);
+        unsafe {
+            gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+            gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+        }
+        let name = if (major, minor) >= (4, 6) {
+            Some("glSpecializeShader")
+        } else if has_extension("GL_ARB_gl_spirv") {
+            Some("glSpecializeShaderARB")
+        } else {
+            None
+        };
+
+        let function = name.and_then(|name| {
+            let address = gl_loader::get_proc_address(name);
+            if address.is_null() {
+                None
+            } else {
+                Some(unsafe { mem::transmute::<*const (), SpecializeShader>(address) })
+            }
+        });
+        cached.set(Some(function));
+        function
+    })
+}
+
+pub trait SpecializationConstant: Copy {
+    fn to_bits(self) -> u32;
+}
+
+impl SpecializationConstant for bool {
+    fn to_bits(self) -> u32 {
+        self as u32
+    }
+}
+
+impl SpecializationConstant for i32 {
+    fn to_bits(self) -> u32 {
+        self as u32
+    }
+}
+
+impl SpecializationConstant for u32 {
+    fn to_bits(self) -> u32 {
+        self
+    }
+}
+
+impl SpecializationConstant for f32 {
+    fn to_bits(self) -> u32 {
+        f32::to_bits(self)
+    }
+}
+
+// Entry point and values of the `layout(constant_id = N)` constants a SPIR-V
+// module is specialized with. Constants not given keep their default value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Specialization {
+    pub entry_point: String,
+    pub constants: Vec<(u32, u32)>,
+}
+
+impl Default for Specialization {
+    fn default() -> Specialization {
+        Specialization {
+            entry_point: "main".to_string(),
+            constants: Vec::new(),
+        }
+    }
+}
+
+impl Specialization {
+    pub fn new() -> Specialization {
+        Specialization::default()
+    }
+
+    pub fn with_entry_point(mut self, entry_point: &str) -> Specialization {
+        self.entry_point = entry_point.to_string();
+        self
+    }
+
+    pub fn with_constant<T: SpecializationConstant>(mut self, id: u32, value: T) -> Specialization {
+        self.constants.retain(|&(other, _)| other != id);
+        self.constants.push((id, value.to_bits()));
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpirvModule {
+    pub binary: Vec<u8>,
+    pub specialization: Specialization,
+}
+
+pub fn is_spirv_path(path: &Path) -> bool {
+    path.extension().and_then(|extension| extension.to_str()) == Some("spv")
+}
+
+impl Shader {
+    // Loads a precompiled SPIR-V module, e.g. `phong.frag.spv`, whose stage
+    // is given by the extension before `.spv` unless `shader_type` is set.
+    // A program can't mix SPIR-V and GLSL shaders, and uniforms are only
+    // reflected by name when the module kept its debug names, by location
+    // (`"3"`) otherwise.
+    pub fn load_spirv(
+        path: &Path,
+        shader_type: Option<ShaderType>,
+        specialization: &Specialization,
+    ) -> Result<Shader, ShaderError> {
+        #[cfg(feature = "debug")]
+        println!("[NFO] Loading SPIR-V shader {}", path.display());
+
+        let specialize = specialize_shader().ok_or(ShaderError::SpirvUnsupported)?;
+
+        let binary = fs::read(path).map_err(|error| ShaderError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let magic = binary
+            .get(..4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]));
+        if binary.len() % 4 != 0 || magic != Some(SPIR_V_MAGIC) {
+            return Err(ShaderError::Compile {
+                path: path.to_path_buf(),
+                log: "not a little endian SPIR-V module".to_string(),
+            });
+        }
+
+        let detected = shader_type.or_else(|| get_shader_type(path));
+        let gl_type = get_gl_shader_type(&detected)
+            .ok_or_else(|| ShaderError::UnknownExtension(path.to_path_buf()))?;
+        let entry_point = CString::new(specialization.entry_point.as_bytes())?;
+        let (indices, values): (Vec<u32>, Vec<u32>) =
+            specialization.constants.iter().cloned().unzip();
+
+        unsafe {
+            let addr = gl::CreateShader(gl_type);
+            gl::ShaderBinary(
+                1,
+                &addr,
+                SHADER_BINARY_FORMAT_SPIR_V,
+                binary.as_ptr() as *const c_void,
+                binary.len() as i32,
+            );
+            specialize(
+                addr,
+                entry_point.as_ptr(),
+                indices.len() as u32,
+                indices.as_ptr(),
+                values.as_ptr(),
+            );
+
+            let mut status: i32 = 0;
+            gl::GetShaderiv(addr, gl::COMPILE_STATUS, &mut status);
+            if status == i32::from(gl::FALSE) {
+                let log = shader_info_log(addr);
+                gl::DeleteShader(addr);
+
+                #[cfg(feature = "debug")]
+                eprintln!(
+                    "[ERR] Couldn't specialize shader {}, log:\n{}",
+                    path.display(),
+                    log
+                );
+
+                return Err(ShaderError::Compile {
+                    path: path.to_path_buf(),
+                    log,
+                });
+            }
+
+            Ok(Shader {
+                addr,
+                path: path.to_string_lossy().into_owned(),
+                source: String::new(),
+                includes: Vec::new(),
+                shader_type: detected.unwrap(),
+                explicit_type: shader_type.is_some(),
+                preprocessor: Preprocessor::default(),
+                spirv: Some(SpirvModule {
+                    binary,
+                    specialization: specialization.clone(),
+                }),
+            })
+        }
+    }
+}
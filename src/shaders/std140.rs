@@ -0,0 +1,238 @@
+use cgmath::{Matrix2, Matrix3, Matrix4, Vector2, Vector3, Vector4};
+
+// A value that can be laid out following the std140 rules of an interface
+// block. Structs implement it by writing their fields in declaration order,
+// which is what `impl_std140!` generates.
+pub trait Std140 {
+    const ALIGN: usize;
+
+    fn write_std140(&self, writer: &mut Std140Writer);
+}
+
+#[derive(Debug, Default)]
+pub struct Std140Writer {
+    data: Vec<u8>,
+}
+
+impl Std140Writer {
+    pub fn new() -> Std140Writer {
+        Std140Writer { data: Vec::new() }
+    }
+
+    pub fn align(&mut self, align: usize) {
+        let padding = (align - self.data.len() % align) % align;
+        self.data.resize(self.data.len() + padding, 0);
+    }
+
+    pub fn write<T: Std140 + ?Sized>(&mut self, value: &T) {
+        self.align(T::ALIGN);
+        value.write_std140(self);
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.align(16);
+        self.data
+    }
+}
+
+pub fn to_std140<T: Std140>(value: &T) -> Vec<u8> {
+    let mut writer = Std140Writer::new();
+    writer.write(value);
+    writer.finish()
+}
+
+impl Std140 for f32 {
+    const ALIGN: usize = 4;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.push(&self.to_ne_bytes());
+    }
+}
+
+impl Std140 for i32 {
+    const ALIGN: usize = 4;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.push(&self.to_ne_bytes());
+    }
+}
+
+impl Std140 for u32 {
+    const ALIGN: usize = 4;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.push(&self.to_ne_bytes());
+    }
+}
+
+impl Std140 for bool {
+    const ALIGN: usize = 4;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.push(&u32::from(*self).to_ne_bytes());
+    }
+}
+
+impl<T: Std140> Std140 for Vector2<T> {
+    const ALIGN: usize = 2 * T::ALIGN;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        self.x.write_std140(writer);
+        self.y.write_std140(writer);
+    }
+}
+
+impl<T: Std140> Std140 for Vector3<T> {
+    const ALIGN: usize = 4 * T::ALIGN;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        self.x.write_std140(writer);
+        self.y.write_std140(writer);
+        self.z.write_std140(writer);
+    }
+}
+
+impl<T: Std140> Std140 for Vector4<T> {
+    const ALIGN: usize = 4 * T::ALIGN;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        self.x.write_std140(writer);
+        self.y.write_std140(writer);
+        self.z.write_std140(writer);
+        self.w.write_std140(writer);
+    }
+}
+
+// Matrices are stored as arrays of column vectors, each padded to a vec4.
+impl Std140 for Matrix2<f32> {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write(&[self.x, self.y]);
+    }
+}
+
+impl Std140 for Matrix3<f32> {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write(&[self.x, self.y, self.z]);
+    }
+}
+
+impl Std140 for Matrix4<f32> {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write(&[self.x, self.y, self.z, self.w]);
+    }
+}
+
+// Array elements are aligned and strided to a multiple of a vec4.
+impl<T: Std140, const N: usize> Std140 for [T; N] {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        self[..].write_std140(writer);
+    }
+}
+
+impl<T: Std140> Std140 for [T] {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        for element in self {
+            writer.align(16);
+            element.write_std140(writer);
+        }
+        writer.align(16);
+    }
+}
+
+#[macro_export]
+macro_rules! impl_std140 {
+    ($name:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::shaders::std140::Std140 for $name {
+            const ALIGN: usize = 16;
+
+            fn write_std140(&self, writer: &mut $crate::shaders::std140::Std140Writer) {
+                $(writer.write(&self.$field);)*
+                writer.align(16);
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::CameraBlock;
+
+    fn f32_at(data: &[u8], offset: usize) -> f32 {
+        f32::from_ne_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    }
+
+    #[test]
+    fn camera_block_layout() {
+        let block = CameraBlock {
+            projection: Matrix4::from_scale(2.0),
+            view: Matrix4::from_scale(3.0),
+            eye_pos: Vector4::new(4.0, 5.0, 6.0, 7.0),
+        };
+        let data = to_std140(&block);
+
+        assert_eq!(data.len(), 144);
+        assert_eq!(f32_at(&data, 0), 2.0);
+        assert_eq!(f32_at(&data, 60), 1.0);
+        assert_eq!(f32_at(&data, 64), 3.0);
+        assert_eq!(f32_at(&data, 128), 4.0);
+        assert_eq!(f32_at(&data, 140), 7.0);
+    }
+
+    struct Mixed {
+        a: f32,
+        b: Vector3<f32>,
+        c: f32,
+        d: Vector2<f32>,
+        e: [f32; 2],
+    }
+
+    crate::impl_std140!(Mixed { a, b, c, d, e });
+
+    #[test]
+    fn scalars_pack_after_vec3() {
+        let data = to_std140(&Mixed {
+            a: 1.0,
+            b: Vector3::new(2.0, 3.0, 4.0),
+            c: 5.0,
+            d: Vector2::new(6.0, 7.0),
+            e: [8.0, 9.0],
+        });
+
+        // vec3 aligns to 16 but the next scalar fills its last 4 bytes, the
+        // array elements are strided to 16.
+        let offsets = [
+            (0, 1.0),
+            (16, 2.0),
+            (24, 4.0),
+            (28, 5.0),
+            (32, 6.0),
+            (36, 7.0),
+            (48, 8.0),
+            (64, 9.0),
+        ];
+        for (offset, value) in offsets.iter() {
+            assert_eq!(f32_at(&data, *offset), *value, "offset {}", offset);
+        }
+        assert_eq!(data.len(), 80);
+    }
+}
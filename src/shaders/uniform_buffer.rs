@@ -0,0 +1,155 @@
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+
+use gl;
+
+use super::std140::{to_std140, Std140};
+
+thread_local! {
+    static USED_BINDINGS: RefCell<Vec<bool>> = const { RefCell::new(Vec::new()) };
+}
+
+fn acquire_binding() -> u32 {
+    USED_BINDINGS.with(|used| {
+        let mut used = used.borrow_mut();
+        match used.iter().position(|&taken| !taken) {
+            Some(binding) => {
+                used[binding] = true;
+                binding as u32
+            }
+            None => {
+                used.push(true);
+                (used.len() - 1) as u32
+            }
+        }
+    })
+}
+
+// Two buffers can't share a binding point: the first one dropped would free it
+// while the other is still bound there.
+fn reserve_binding(binding: u32) {
+    USED_BINDINGS.with(|used| {
+        let mut used = used.borrow_mut();
+        let index = binding as usize;
+        if used.len() <= index {
+            used.resize(index + 1, false);
+        }
+        if used[index] {
+            panic!("uniform buffer binding {} is already used", binding);
+        }
+        used[index] = true;
+    })
+}
+
+fn release_binding(binding: u32) {
+    USED_BINDINGS.with(|used| {
+        if let Some(taken) = used.borrow_mut().get_mut(binding as usize) {
+            *taken = false;
+        }
+    })
+}
+
+#[derive(Debug)]
+pub struct UniformBuffer<T: Std140> {
+    pub addr: u32,
+    pub binding: u32,
+    size: usize,
+    marker: PhantomData<T>,
+}
+
+impl<T: Std140> Drop for UniformBuffer<T> {
+    fn drop(&mut self) {
+        release_binding(self.binding);
+        unsafe {
+            gl::DeleteBuffers(1, &self.addr);
+        }
+    }
+}
+
+impl<T: Std140> UniformBuffer<T> {
+    // Picks the first binding point not used by another uniform buffer.
+    pub fn new(value: &T) -> UniformBuffer<T> {
+        UniformBuffer::with_binding(acquire_binding(), value)
+    }
+
+    // Panics if another uniform buffer uses `binding`.
+    pub fn with_binding(binding: u32, value: &T) -> UniformBuffer<T> {
+        reserve_binding(binding);
+
+        let mut addr: u32 = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut addr);
+        }
+
+        let mut buffer = UniformBuffer {
+            addr,
+            binding,
+            size: 0,
+            marker: PhantomData,
+        };
+        buffer.update(value);
+        buffer.bind();
+        buffer
+    }
+
+    pub fn update(&mut self, value: &T) {
+        let data = to_std140(value);
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.addr);
+            if data.len() == self.size {
+                gl::BufferSubData(
+                    gl::UNIFORM_BUFFER,
+                    0,
+                    data.len() as isize,
+                    data.as_ptr() as *const c_void,
+                );
+            } else {
+                gl::BufferData(
+                    gl::UNIFORM_BUFFER,
+                    data.len() as isize,
+                    data.as_ptr() as *const c_void,
+                    gl::DYNAMIC_DRAW,
+                );
+                self.size = data.len();
+            }
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, self.binding, self.addr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Bindings are per thread, and every test runs on its own thread.
+    #[test]
+    fn acquire_skips_reserved_bindings() {
+        reserve_binding(0);
+        reserve_binding(2);
+        assert_eq!(acquire_binding(), 1);
+        assert_eq!(acquire_binding(), 3);
+        release_binding(2);
+        assert_eq!(acquire_binding(), 2);
+    }
+
+    #[test]
+    fn released_bindings_can_be_reserved_again() {
+        reserve_binding(4);
+        release_binding(4);
+        reserve_binding(4);
+    }
+
+    #[test]
+    #[should_panic(expected = "binding 3 is already used")]
+    fn double_reservation_panics() {
+        reserve_binding(3);
+        reserve_binding(3);
+    }
+}
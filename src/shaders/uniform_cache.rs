@@ -0,0 +1,88 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::mem;
+use std::slice;
+
+use cgmath::{Matrix2, Matrix3, Matrix4, Vector2, Vector3, Vector4};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UniformStats {
+    pub issued: u64,
+    pub skipped: u64,
+}
+
+/// Values compared byte for byte by the cache.
+///
+/// # Safety
+///
+/// Every byte of the value must be initialized, i.e. the type has no padding.
+/// cgmath vectors and matrices are `repr(C)` arrays of their scalars.
+pub(crate) unsafe trait Plain {}
+
+unsafe impl Plain for f32 {}
+unsafe impl Plain for i32 {}
+unsafe impl Plain for u32 {}
+unsafe impl Plain for bool {}
+unsafe impl<T: Plain> Plain for Vector2<T> {}
+unsafe impl<T: Plain> Plain for Vector3<T> {}
+unsafe impl<T: Plain> Plain for Vector4<T> {}
+unsafe impl Plain for Matrix2<f32> {}
+unsafe impl Plain for Matrix3<f32> {}
+unsafe impl Plain for Matrix4<f32> {}
+unsafe impl<T: Plain> Plain for [T] {}
+
+// Shadow copy of the last value written to each uniform location of a
+// program, so setting a uniform to the value it already holds doesn't reach
+// the driver. Values are compared bit for bit.
+#[derive(Debug, Default)]
+pub struct UniformCache {
+    values: RefCell<HashMap<i32, Vec<u8>>>,
+    stats: Cell<UniformStats>,
+}
+
+impl UniformCache {
+    // Calls `upload` unless `value` is what was last uploaded to `location`.
+    pub(crate) fn upload<T: Plain + ?Sized, F: FnOnce()>(
+        &self,
+        location: i32,
+        value: &T,
+        upload: F,
+    ) {
+        // Sound since `Plain` values have no uninitialized bytes.
+        let bytes = unsafe {
+            slice::from_raw_parts(value as *const T as *const u8, mem::size_of_val(value))
+        };
+
+        let mut stats = self.stats.get();
+        let mut values = self.values.borrow_mut();
+        match values.get_mut(&location) {
+            Some(last) if last.as_slice() == bytes => stats.skipped += 1,
+            Some(last) => {
+                last.clear();
+                last.extend_from_slice(bytes);
+                upload();
+                stats.issued += 1;
+            }
+            None => {
+                values.insert(location, bytes.to_vec());
+                upload();
+                stats.issued += 1;
+            }
+        }
+        self.stats.set(stats);
+    }
+
+    // Forgets the shadow values, e.g. after the uniforms were set behind the
+    // cache's back with raw GL calls.
+    pub fn clear(&self) {
+        self.values.borrow_mut().clear();
+    }
+
+    pub fn stats(&self) -> UniformStats {
+        self.stats.get()
+    }
+
+    pub fn reset_stats(&self) {
+        self.stats.set(UniformStats::default());
+    }
+}
@@ -0,0 +1,311 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use glsl_lang::ast::{
+    DeclarationData, ExternalDeclarationData, StorageQualifierData, TranslationUnit, TypeQualifier,
+    TypeQualifierSpecData,
+};
+use glsl_lang::parse::DefaultParse;
+
+use super::preprocessor::PreprocessedSource;
+use super::*;
+
+// Checks done without a GL context: what can be caught before the driver sees
+// the shaders, i.e. includes, stage detection and GLSL syntax.
+#[derive(Debug)]
+pub enum ValidationError {
+    Shader(ShaderError),
+    UnknownUniform {
+        path: PathBuf,
+        line: usize,
+        name: String,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::Shader(error) => write!(f, "{}", error),
+            ValidationError::UnknownUniform { path, line, name } => write!(
+                f,
+                "{}:{}: uniform {} isn't declared by any shader",
+                path.display(),
+                line,
+                name
+            ),
+        }
+    }
+}
+
+impl Error for ValidationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ValidationError::Shader(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ShaderError> for ValidationError {
+    fn from(error: ShaderError) -> ValidationError {
+        ValidationError::Shader(error)
+    }
+}
+
+// Uniforms and uniform blocks declared by a shader, in the branches left by
+// the preprocessor.
+#[derive(Debug, Clone, Default)]
+pub struct DeclaredUniforms {
+    pub uniforms: HashSet<String>,
+    pub blocks: HashSet<String>,
+}
+
+impl DeclaredUniforms {
+    pub fn extend(&mut self, other: DeclaredUniforms) {
+        self.uniforms.extend(other.uniforms);
+        self.blocks.extend(other.blocks);
+    }
+
+    // Elements of arrays and members of structs, like `lights[1].position`,
+    // are checked by the name of the declared uniform.
+    pub fn contains(&self, reference: &UniformReference) -> bool {
+        if reference.block {
+            self.blocks.contains(&reference.name)
+        } else {
+            let end = reference
+                .name
+                .find(['[', '.'])
+                .unwrap_or(reference.name.len());
+            self.uniforms.contains(&reference.name[..end])
+        }
+    }
+}
+
+fn is_uniform(qualifier: &TypeQualifier) -> bool {
+    qualifier.qualifiers.iter().any(|spec| match &spec.content {
+        TypeQualifierSpecData::Storage(storage) => {
+            matches!(storage.content, StorageQualifierData::Uniform)
+        }
+        _ => false,
+    })
+}
+
+fn declared_uniforms(unit: &TranslationUnit) -> DeclaredUniforms {
+    let mut declared = DeclaredUniforms::default();
+    for declaration in &unit.0 {
+        let declaration = match &declaration.content {
+            ExternalDeclarationData::Declaration(declaration) => declaration,
+            _ => continue,
+        };
+
+        match &declaration.content {
+            DeclarationData::InitDeclaratorList(list) => {
+                let uniform = list.head.ty.qualifier.as_ref().is_some_and(is_uniform);
+                if !uniform {
+                    continue;
+                }
+                let names = list.head.name.iter().map(|name| name.as_str());
+                let tail = list.tail.iter().map(|decl| decl.ident.ident.as_str());
+                declared
+                    .uniforms
+                    .extend(names.chain(tail).map(String::from));
+            }
+            DeclarationData::Block(block) if is_uniform(&block.qualifier) => {
+                declared.blocks.insert(block.name.as_str().to_string());
+                // Members of a block without instance name are used as is.
+                if block.identifier.is_none() {
+                    let fields = block.fields.iter().flat_map(|field| &field.identifiers);
+                    declared
+                        .uniforms
+                        .extend(fields.map(|field| field.ident.as_str().to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+    declared
+}
+
+// Parses a preprocessed shader, errors point to the original files.
+pub fn parse_shader(
+    path: &Path,
+    processed: &PreprocessedSource,
+) -> Result<DeclaredUniforms, ShaderError> {
+    let unit =
+        <TranslationUnit as DefaultParse>::parse(processed.source.as_str()).map_err(|error| {
+            ShaderError::Compile {
+                path: path.to_path_buf(),
+                log: processed.source_map.translate_log(&error.to_string()),
+            }
+        })?;
+    Ok(declared_uniforms(&unit))
+}
+
+// A uniform name given as a string literal to one of the `Program` setters,
+// or a block name given to `bind_uniform_block`/`bind_uniform_buffer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UniformReference {
+    pub line: usize,
+    pub name: String,
+    pub block: bool,
+}
+
+const SETTER_TYPES: [&str; 21] = [
+    "uniform",
+    "float",
+    "int",
+    "uint",
+    "bool",
+    "sampler",
+    "vec2",
+    "vec3",
+    "vec4",
+    "ivec2",
+    "ivec3",
+    "ivec4",
+    "mat2",
+    "mat3",
+    "mat4",
+    "float_array",
+    "int_array",
+    "vec2_array",
+    "vec3_array",
+    "vec4_array",
+    "mat4_array",
+];
+
+fn is_uniform_setter(method: &str) -> Option<bool> {
+    match method {
+        "bind_uniform_block" | "bind_uniform_buffer" => Some(true),
+        _ => method
+            .strip_prefix("set_")
+            .filter(|kind| SETTER_TYPES.contains(kind))
+            .map(|_| false),
+    }
+}
+
+// The call can be split over several lines, its line is the one of the name.
+pub fn referenced_uniforms(src: &str) -> Vec<UniformReference> {
+    let mut references = Vec::new();
+    for (dot, _) in src.match_indices('.') {
+        let rest = &src[dot + 1..];
+        let method_len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let block = match is_uniform_setter(&rest[..method_len]) {
+            Some(block) => block,
+            None => continue,
+        };
+
+        let args = rest[method_len..].trim_start();
+        let name = args
+            .strip_prefix('(')
+            .map(str::trim_start)
+            .and_then(|args| args.strip_prefix('"'))
+            .and_then(|args| Some((args, &args[..args.find(['"', '\n'])?])));
+        if let Some((name_start, name)) = name {
+            let offset = src.len() - name_start.len();
+            references.push(UniformReference {
+                line: src[..offset].matches('\n').count() + 1,
+                name: name.to_string(),
+                block,
+            });
+        }
+    }
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn declared(src: &str) -> DeclaredUniforms {
+        declared_uniforms(&<TranslationUnit as DefaultParse>::parse(src).unwrap())
+    }
+
+    fn names(set: &HashSet<String>) -> Vec<&str> {
+        let mut names: Vec<&str> = set.iter().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    fn reference(line: usize, name: &str, block: bool) -> UniformReference {
+        UniformReference {
+            line,
+            name: name.to_string(),
+            block,
+        }
+    }
+
+    #[test]
+    fn setters_are_matched_across_lines_and_in_macros() {
+        let src = "program.set_float(\"time\", t)?;\n\
+                   program\n    .set_vec3(\n        \"color\",\n        &color,\n    )?;\n\
+                   assert!(program.set_mat4(\"model\", &m).is_ok());\n\
+                   pipeline.bind_uniform_block(\"Lights\", 0)?;\n\
+                   program.set_float(&format!(\"lights[{}]\", i), 1.0)?;\n\
+                   program.set_double(\"unknown\", 1.0)?;\n";
+        assert_eq!(
+            referenced_uniforms(src),
+            vec![
+                reference(1, "time", false),
+                reference(4, "color", false),
+                reference(7, "model", false),
+                reference(8, "Lights", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn uniforms_in_blocks_and_arrays_are_declared() {
+        let declared = declared(
+            "uniform float time, scale;\n\
+             uniform vec3 lights[4];\n\
+             uniform Material { vec4 albedo; float roughness; };\n\
+             uniform Camera { mat4 view; } camera;\n\
+             in vec3 normal;\n\
+             void main() {}\n",
+        );
+        assert_eq!(
+            names(&declared.uniforms),
+            vec!["albedo", "lights", "roughness", "scale", "time"]
+        );
+        assert_eq!(names(&declared.blocks), vec!["Camera", "Material"]);
+
+        assert!(declared.contains(&reference(1, "lights[2]", false)));
+        assert!(declared.contains(&reference(1, "Camera", true)));
+        assert!(!declared.contains(&reference(1, "view", false)));
+        assert!(!declared.contains(&reference(1, "time", true)));
+    }
+
+    #[test]
+    fn only_the_taken_ifdef_branch_is_declared() {
+        let dir = std::env::temp_dir().join(format!("peglrs-ifdef-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("branches.frag");
+        std::fs::write(
+            &path,
+            "#version 450\n\
+             #ifdef SHADOWS\n\
+             uniform sampler2D shadow_map;\n\
+             #else\n\
+             uniform float ambient;\n\
+             #endif\n\
+             void main() {}\n",
+        )
+        .unwrap();
+
+        let shadows = Preprocessor::default().with_define("SHADOWS", "");
+        let processed = shadows.process(&path).unwrap();
+        let declared = parse_shader(&path, &processed).unwrap();
+        assert_eq!(names(&declared.uniforms), vec!["shadow_map"]);
+
+        let processed = Preprocessor::default().process(&path).unwrap();
+        let declared = parse_shader(&path, &processed).unwrap();
+        assert_eq!(names(&declared.uniforms), vec!["ambient"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::*;
+
+type VariantKey = (PathBuf, Vec<(String, String)>);
+
+// Compiled permutations of shader sources, keyed by path and define set. The
+// define set is sorted so the order the defines are given in doesn't matter,
+// and a name given twice keeps its last value like `Preprocessor::with_define`.
+#[derive(Debug, Default)]
+pub struct ShaderVariants {
+    variants: HashMap<VariantKey, Rc<Shader>>,
+}
+
+fn variant_key(path: &Path, defines: &[(&str, &str)]) -> VariantKey {
+    let mut resolved: Vec<(String, String)> = Vec::new();
+    for (name, value) in defines {
+        resolved.retain(|(defined, _)| defined != name);
+        resolved.push((name.to_string(), value.to_string()));
+    }
+    resolved.sort();
+    (path.to_path_buf(), resolved)
+}
+
+impl ShaderVariants {
+    pub fn new() -> ShaderVariants {
+        ShaderVariants {
+            variants: HashMap::new(),
+        }
+    }
+
+    pub fn get(
+        &mut self,
+        path: &Path,
+        defines: &[(&str, &str)],
+    ) -> Result<Rc<Shader>, ShaderError> {
+        let key = variant_key(path, defines);
+        if let Some(shader) = self.variants.get(&key) {
+            return Ok(Rc::clone(shader));
+        }
+
+        let defines: Vec<(&str, &str)> = key
+            .1
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        let shader = Rc::new(Shader::load_shader_with_defines(path, &defines)?);
+        self.variants.insert(key, Rc::clone(&shader));
+        Ok(shader)
+    }
+
+    pub fn len(&self) -> usize {
+        self.variants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variants.is_empty()
+    }
+
+    // Drops every cached permutation of `path`, e.g. after it changed on disk.
+    pub fn invalidate(&mut self, path: &Path) {
+        self.variants
+            .retain(|(variant_path, _), shader| variant_path != path && !shader.depends_on(path));
+    }
+
+    pub fn clear(&mut self) {
+        self.variants.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn define_order_does_not_change_the_key() {
+        let path = Path::new("shader.frag");
+        assert_eq!(
+            variant_key(path, &[("A", "1"), ("B", "2")]),
+            variant_key(path, &[("B", "2"), ("A", "1")])
+        );
+        assert_ne!(
+            variant_key(path, &[("A", "1")]),
+            variant_key(path, &[("A", "2")])
+        );
+    }
+
+    #[test]
+    fn duplicate_defines_keep_the_last_value() {
+        let path = Path::new("shader.frag");
+        let key = variant_key(path, &[("A", "1"), ("B", "2"), ("A", "3")]);
+        assert_eq!(key, variant_key(path, &[("A", "3"), ("B", "2")]));
+
+        let preprocessor = Preprocessor::default()
+            .with_define("A", "1")
+            .with_define("B", "2")
+            .with_define("A", "3");
+        let mut defines = preprocessor.defines;
+        defines.sort();
+        assert_eq!(defines, key.1);
+    }
+}
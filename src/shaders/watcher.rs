@@ -0,0 +1,195 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::*;
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+// Polls the modification time of every shader used by the watched programs,
+// so it doesn't rely on any platform notification API.
+#[derive(Debug)]
+pub struct ShaderWatcher {
+    interval: Duration,
+    last_poll: Option<Instant>,
+    mtimes: HashMap<PathBuf, Option<SystemTime>>,
+    programs: Vec<Weak<RefCell<Program>>>,
+}
+
+impl ShaderWatcher {
+    pub fn new(interval: Duration) -> ShaderWatcher {
+        ShaderWatcher {
+            interval,
+            last_poll: None,
+            mtimes: HashMap::new(),
+            programs: Vec::new(),
+        }
+    }
+
+    pub fn watch(&mut self, program: &Rc<RefCell<Program>>) {
+        if let Some(manifest) = &program.borrow().manifest {
+            self.track(Path::new(manifest));
+        }
+        for shader in &program.borrow().shaders {
+            self.track_shader(shader);
+        }
+        self.programs.push(Rc::downgrade(program));
+    }
+
+    pub fn track(&mut self, path: &Path) {
+        self.mtimes
+            .entry(path.to_path_buf())
+            .or_insert_with(|| modified_time(path));
+    }
+
+    fn track_shader(&mut self, shader: &Shader) {
+        self.track(Path::new(&shader.path));
+        for include in &shader.includes {
+            self.track(Path::new(include));
+        }
+    }
+
+    pub fn changed_paths(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        for (path, mtime) in self.mtimes.iter_mut() {
+            let current = modified_time(path);
+            if current != *mtime {
+                *mtime = current;
+                changed.push(path.clone());
+            }
+        }
+
+        changed
+    }
+
+    // Recompiles every modified shader and relinks the programs using it.
+    // A program that fails to rebuild keeps running its previous version.
+    pub fn poll(&mut self) -> Vec<ShaderError> {
+        let now = Instant::now();
+        if let Some(last_poll) = self.last_poll {
+            if now.duration_since(last_poll) < self.interval {
+                return Vec::new();
+            }
+        }
+        self.last_poll = Some(now);
+
+        self.programs.retain(|program| program.upgrade().is_some());
+
+        let changed = self.changed_paths();
+        if changed.is_empty() {
+            return Vec::new();
+        }
+
+        #[cfg(feature = "debug")]
+        for path in &changed {
+            println!("[NFO] Shader source {} changed on disk", path.display());
+        }
+
+        let mut errors = Vec::new();
+        // Keyed by the old shader, which is kept alive so its address can't be
+        // reused. A failed reload is stored as `None` and only reported once.
+        let mut reloaded: HashMap<*const Shader, (Rc<Shader>, Option<Rc<Shader>>)> = HashMap::new();
+        let mut rebuilt = Vec::new();
+        for program in self.programs.iter().filter_map(|program| program.upgrade()) {
+            // An edited manifest can change anything, so rebuild from scratch.
+            let manifest = program.borrow().manifest.clone();
+            if let Some(manifest) = manifest {
+                if changed.iter().any(|path| path == Path::new(&manifest)) {
+                    match Program::load_manifest(Path::new(&manifest)) {
+                        Ok(new_program) => {
+                            *program.borrow_mut() = new_program;
+                            rebuilt.push(program);
+                        }
+                        Err(err) => errors.push(err),
+                    }
+                    continue;
+                }
+            }
+
+            let mut dirty = false;
+            let mut shaders = Vec::new();
+
+            for shader in &program.borrow().shaders {
+                let key = Rc::as_ptr(shader);
+                if !reloaded.contains_key(&key)
+                    && changed.iter().any(|path| shader.depends_on(path))
+                {
+                    let new_shader = match shader.reload() {
+                        Ok(new_shader) => Some(Rc::new(new_shader)),
+                        Err(err) => {
+                            errors.push(err);
+                            None
+                        }
+                    };
+                    reloaded.insert(key, (Rc::clone(shader), new_shader));
+                }
+
+                match reloaded
+                    .get(&key)
+                    .and_then(|(_, new_shader)| new_shader.as_ref())
+                {
+                    Some(new_shader) => {
+                        dirty = true;
+                        shaders.push(Rc::clone(new_shader));
+                    }
+                    None => shaders.push(Rc::clone(shader)),
+                }
+            }
+
+            if !dirty {
+                continue;
+            }
+
+            let relinked = program.borrow().relink(&shaders);
+            match relinked {
+                Ok(new_program) => *program.borrow_mut() = new_program,
+                Err(err) => errors.push(err),
+            }
+        }
+
+        for new_shader in reloaded
+            .values()
+            .filter_map(|(_, new_shader)| new_shader.as_ref())
+        {
+            self.track_shader(new_shader);
+        }
+        for program in rebuilt {
+            for shader in &program.borrow().shaders {
+                self.track_shader(shader);
+            }
+        }
+
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_paths_reports_a_rewrite_once() {
+        let path = std::env::temp_dir().join(format!("peglrs-watcher-{}.glsl", std::process::id()));
+        fs::write(&path, "void main() {}").unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000))
+            .unwrap();
+
+        let mut watcher = ShaderWatcher::new(Duration::from_millis(0));
+        watcher.track(&path);
+        assert!(watcher.changed_paths().is_empty());
+
+        fs::write(&path, "void main() { }").unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(2_000_000))
+            .unwrap();
+        assert_eq!(watcher.changed_paths(), vec![path.clone()]);
+        assert!(watcher.changed_paths().is_empty());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
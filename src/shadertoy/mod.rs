@@ -0,0 +1,357 @@
+use std::cell::RefCell;
+use std::mem;
+use std::path::Path;
+use std::rc::Rc;
+
+use cgmath::{Vector3, Vector4};
+
+use crate::frame::fbo::Framebuffer;
+use crate::mesh::data::MeshData;
+use crate::mesh::gpu::GpuMesh;
+use crate::shaders::preprocessor::Preprocessor;
+use crate::shaders::{Program, Shader, ShaderError, ShaderType};
+
+pub const VERTEX_SHADER: &str = "data/shaders/shadertoy/quad.vs";
+
+// Declarations Shadertoy provides to every pass. iChannelTime, iDate and
+// iSampleRate aren't supported.
+const PRELUDE: &str = "#version 330 core
+uniform vec3 iResolution;
+uniform float iTime;
+uniform float iTimeDelta;
+uniform int iFrame;
+uniform vec4 iMouse;
+uniform vec3 iChannelResolution[4];
+uniform sampler2D iChannel0;
+uniform sampler2D iChannel1;
+uniform sampler2D iChannel2;
+uniform sampler2D iChannel3;
+out vec4 shadertoy_FragColor;
+";
+
+const EPILOGUE: &str = "void main()
+{
+    shadertoy_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+    mainImage(shadertoy_FragColor, gl_FragCoord.xy);
+}
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Buffer {
+    A,
+    B,
+    C,
+    D,
+}
+
+impl Buffer {
+    pub const ALL: [Buffer; 4] = [Buffer::A, Buffer::B, Buffer::C, Buffer::D];
+
+    fn index(self) -> usize {
+        match self {
+            Buffer::A => 0,
+            Buffer::B => 1,
+            Buffer::C => 2,
+            Buffer::D => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    // Last frame rendered by the buffer, so a buffer can read itself or a
+    // buffer rendered after it, which then lags by one frame like on
+    // Shadertoy.
+    Buffer(Buffer),
+    // A GL_TEXTURE_2D, with its size for iChannelResolution.
+    Texture { addr: u32, width: i32, height: i32 },
+}
+
+// A fragment shader written with the Shadertoy conventions: it only defines
+// `mainImage(out vec4 fragColor, in vec2 fragCoord)` and uses the uniforms of
+// the prelude. The uniforms and the `main` calling `mainImage` are added by
+// the preprocessor, so includes, defines and compile logs work as usual.
+pub fn load_shader(path: &Path) -> Result<Shader, ShaderError> {
+    Shader::load_shader_as(path, ShaderType::FRAGMENT, &preprocessor())
+}
+
+pub fn preprocessor() -> Preprocessor {
+    Preprocessor::default()
+        .with_prelude(PRELUDE)
+        .with_epilogue(EPILOGUE)
+}
+
+// Shadertoy shaders have no `#version`, and define `mainImage`.
+pub fn is_shadertoy_source(src: &str) -> bool {
+    let has_version = src
+        .lines()
+        .any(|line| line.trim_start().starts_with("#version"));
+    !has_version && src.contains("mainImage")
+}
+
+pub fn load_program(path: &Path) -> Result<Program, ShaderError> {
+    let vertex = Shader::load_shader(Path::new(VERTEX_SHADER))?;
+    let fragment = load_shader(path)?;
+    Program::load_program(&[Rc::new(vertex), Rc::new(fragment)])
+}
+
+#[derive(Debug)]
+struct Pass {
+    program: Rc<RefCell<Program>>,
+    channels: [Option<Channel>; 4],
+}
+
+// Double buffered target of Buffer A-D: passes sample `front`, the buffer
+// renders into `back` then both are swapped.
+#[derive(Debug)]
+struct Target {
+    front: Framebuffer,
+    back: Framebuffer,
+}
+
+impl Target {
+    fn new(width: i32, height: i32) -> Target {
+        let target = Target {
+            front: Framebuffer::new_hdr(width, height),
+            back: Framebuffer::new_hdr(width, height),
+        };
+        for framebuffer in &[&target.front, &target.back] {
+            unsafe {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer.addr);
+                gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+            }
+        }
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        target
+    }
+}
+
+// Renders a Shadertoy: Buffer A to D in order, each into its own target, then
+// the image pass to the default framebuffer.
+#[derive(Debug)]
+pub struct Shadertoy {
+    image: Pass,
+    buffers: [Option<Pass>; 4],
+    targets: [Option<Target>; 4],
+    quad: GpuMesh,
+    width: i32,
+    height: i32,
+    pub time: f32,
+    pub time_delta: f32,
+    pub frame: i32,
+    mouse: Vector4<f32>,
+    mouse_pressed: bool,
+}
+
+fn default_channels(buffers: &[Option<Pass>; 4]) -> [Option<Channel>; 4] {
+    let mut channels = [None; 4];
+    for buffer in Buffer::ALL.iter() {
+        if buffers[buffer.index()].is_some() {
+            channels[buffer.index()] = Some(Channel::Buffer(*buffer));
+        }
+    }
+    channels
+}
+
+impl Shadertoy {
+    pub fn new(image: &Path, width: i32, height: i32) -> Result<Shadertoy, ShaderError> {
+        Ok(Shadertoy {
+            image: Pass {
+                program: Rc::new(RefCell::new(load_program(image)?)),
+                channels: [None; 4],
+            },
+            buffers: [None, None, None, None],
+            targets: [None, None, None, None],
+            quad: MeshData::fs_quad().upload(),
+            width,
+            height,
+            time: 0.0,
+            time_delta: 0.0,
+            frame: 0,
+            mouse: Vector4::new(0.0, 0.0, 0.0, 0.0),
+            mouse_pressed: false,
+        })
+    }
+
+    // Adds a buffer pass. Until channels are set explicitly, iChannel0 to
+    // iChannel3 of every pass are Buffer A to D, when they exist.
+    pub fn with_buffer(mut self, buffer: Buffer, path: &Path) -> Result<Shadertoy, ShaderError> {
+        let index = buffer.index();
+        self.buffers[index] = Some(Pass {
+            program: Rc::new(RefCell::new(load_program(path)?)),
+            channels: [None; 4],
+        });
+        self.targets[index] = Some(Target::new(self.width, self.height));
+
+        let channels = default_channels(&self.buffers);
+        self.image.channels = channels;
+        for pass in self.buffers.iter_mut().flatten() {
+            pass.channels = channels;
+        }
+        Ok(self)
+    }
+
+    // `pass` is the buffer whose input is set, or `None` for the image pass.
+    pub fn set_channel(&mut self, pass: Option<Buffer>, channel: usize, input: Option<Channel>) {
+        let pass = match pass {
+            Some(buffer) => self.buffers[buffer.index()].as_mut(),
+            None => Some(&mut self.image),
+        };
+        if let Some(pass) = pass {
+            pass.channels[channel] = input;
+        }
+    }
+
+    // Programs of every pass, e.g. to hot reload them with a `ShaderWatcher`.
+    pub fn programs(&self) -> Vec<Rc<RefCell<Program>>> {
+        self.buffers
+            .iter()
+            .flatten()
+            .chain(Some(&self.image))
+            .map(|pass| Rc::clone(&pass.program))
+            .collect()
+    }
+
+    // Buffers restart black at the new size.
+    pub fn resize(&mut self, width: i32, height: i32) {
+        self.width = width;
+        self.height = height;
+        for (target, pass) in self.targets.iter_mut().zip(self.buffers.iter()) {
+            if pass.is_some() {
+                *target = Some(Target::new(width, height));
+            }
+        }
+    }
+
+    // `x` and `y` are in pixels from the bottom left corner. Like on
+    // Shadertoy, iMouse.xy is the position while pressed and iMouse.zw the
+    // click position, z is negative once released and w only positive on the
+    // frame of the click.
+    pub fn set_mouse(&mut self, x: f32, y: f32, pressed: bool) {
+        if pressed {
+            if self.mouse_pressed {
+                self.mouse.w = -self.mouse.w.abs();
+            } else {
+                self.mouse.z = x;
+                self.mouse.w = y;
+            }
+            self.mouse.x = x;
+            self.mouse.y = y;
+        } else {
+            self.mouse.z = -self.mouse.z.abs();
+            self.mouse.w = -self.mouse.w.abs();
+        }
+        self.mouse_pressed = pressed;
+    }
+
+    fn channel_input(&self, channel: Option<Channel>) -> Option<(u32, i32, i32)> {
+        match channel? {
+            Channel::Buffer(buffer) => {
+                let target = self.targets[buffer.index()].as_ref()?;
+                let texture = target.front.color_attachment?;
+                Some((texture, self.width, self.height))
+            }
+            Channel::Texture {
+                addr,
+                width,
+                height,
+            } => Some((addr, width, height)),
+        }
+    }
+
+    // Binds the program and inputs of `pass`, which is then drawn by the
+    // caller.
+    fn bind_pass(&self, pass: &Pass) -> Result<(), ShaderError> {
+        let program = pass.program.borrow();
+        program.bind();
+
+        let mut resolutions = [Vector3::new(0.0, 0.0, 0.0); 4];
+        for (unit, channel) in pass.channels.iter().enumerate() {
+            let (texture, width, height) = self.channel_input(*channel).unwrap_or((0, 0, 0));
+            resolutions[unit] = Vector3::new(width as f32, height as f32, 1.0);
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+                gl::BindTexture(gl::TEXTURE_2D, texture);
+            }
+            program.set_sampler(&format!("iChannel{}", unit), unit as u32)?;
+        }
+
+        let resolution = Vector3::new(self.width as f32, self.height as f32, 1.0);
+        program.set_vec3("iResolution", &resolution)?;
+        program.set_float("iTime", self.time)?;
+        program.set_float("iTimeDelta", self.time_delta)?;
+        program.set_int("iFrame", self.frame)?;
+        program.set_vec4("iMouse", &self.mouse)?;
+        // Drivers shrink arrays to the last element the shader reads.
+        if let Some(uniform) = program.uniforms.get("iChannelResolution") {
+            let count = (uniform.size.max(0) as usize).min(resolutions.len());
+            program.set_vec3_array("iChannelResolution", &resolutions[..count])?;
+        }
+        Ok(())
+    }
+
+    // Renders one frame and advances iTime by `dt` seconds.
+    pub fn render(&mut self, dt: f32) -> Result<(), ShaderError> {
+        self.time_delta = dt;
+
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::BLEND);
+            gl::Viewport(0, 0, self.width, self.height);
+        }
+
+        for index in 0..self.buffers.len() {
+            let (pass, target) = match (&self.buffers[index], &self.targets[index]) {
+                (Some(pass), Some(target)) => (pass, target),
+                _ => continue,
+            };
+            unsafe {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, target.back.addr);
+            }
+            self.bind_pass(pass)?;
+            self.quad.draw();
+
+            if let Some(target) = &mut self.targets[index] {
+                mem::swap(&mut target.front, &mut target.back);
+            }
+        }
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        self.bind_pass(&self.image)?;
+        self.quad.draw();
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+        Program::unbind();
+
+        self.time += dt;
+        self.frame += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shadertoy_sources_define_main_image_without_version() {
+        assert!(is_shadertoy_source(
+            "void mainImage(out vec4 color, in vec2 coord) {\n    color = vec4(1.0);\n}\n"
+        ));
+        assert!(!is_shadertoy_source(
+            "#version 450\nvoid mainImage(out vec4 color, in vec2 coord) {}\nvoid main() {}\n"
+        ));
+        assert!(!is_shadertoy_source(
+            "  #version 330 core\nvoid mainImage(out vec4 c, in vec2 p) {}\n"
+        ));
+        assert!(!is_shadertoy_source("void main() {}\n"));
+    }
+}